@@ -0,0 +1,222 @@
+use crate::def_cache::write_def_cache;
+use crate::def_diff::{diff_defs, ContentVersion};
+use crate::def_registry::DefTypeRegistry;
+use crate::def_schema::{find_outdated_schemas, write_schemas};
+use crate::items::ItemPlugin;
+use crate::json_asset_definition::{load_defs_from_disk, load_resolved_defs_from_disk};
+use crate::localization::{read_locale_tables, LocalizedStrings};
+use crate::monsters::MonsterPlugin;
+use crate::prelude::*;
+use crate::world::WorldPlugin;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: monster-tamer [COMMAND]
+
+Without a command the game is started.
+
+Commands:
+  validate-defs [ASSET_FOLDER]             Loads all defs without opening a window, and reports any errors
+                                         and missing translations
+  bake-defs [ASSET_FOLDER]                 Loads all defs, and writes them to the def cache the game starts from
+                                         while the def files are unchanged
+  diff-defs OLD_ASSET_FOLDER NEW_ASSET_FOLDER
+                                         Loads the defs of two versions of the content, and lists the defs
+                                         and fields that were added, removed or changed, and the defs that
+                                         failed to load
+  export-schema [--check] [OUTPUT_FOLDER]  Writes the json schemas for def files, defaults to the assets folder.
+                                         With --check, fails if the existing schemas are out of date instead";
+
+/// Runs the command given on the command line, if any.
+///
+/// Returns the exit code of the command, or `None` when no command was given and the game should start.
+pub fn run_command() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first()?;
+
+    let exit_code = match command.as_str() {
+        "validate-defs" => validate_defs(&args[1..]),
+        "bake-defs" => bake_defs(&args[1..]),
+        "diff-defs" => diff_defs_command(&args[1..]),
+        "export-schema" => export_schema(&args[1..]),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            0
+        }
+        // Flags for the game itself, like `--strict-defs`
+        flag if flag.starts_with("--") => return None,
+        unknown => {
+            eprintln!("Unknown command '{}'\n\n{}", unknown, USAGE);
+            2
+        }
+    };
+
+    Some(exit_code)
+}
+
+/// An app with an asset server and the registered def types, but without a window or the rest of the game,
+/// for commands that load defs.
+pub fn headless_app(asset_folder: &str) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin {
+            asset_folder: asset_folder.to_string(),
+            watch_for_changes: false,
+        })
+        .add_plugin(MonsterPlugin)
+        .add_plugin(ItemPlugin)
+        .add_plugin(WorldPlugin);
+    app
+}
+
+fn validate_defs(args: &[String]) -> i32 {
+    let asset_folder = args.first().map(String::as_str).unwrap_or("assets");
+    let app = headless_app(asset_folder);
+    let registry = app.world.resource::<DefTypeRegistry>();
+
+    let (databases, report) = load_defs_from_disk(
+        registry,
+        app.world.resource::<AssetServer>(),
+        &PathBuf::from(asset_folder),
+    );
+
+    let counts: Vec<String> = registry
+        .counts(&databases)
+        .into_iter()
+        .map(|(type_name, count)| format!("{} {}", count, type_name))
+        .collect();
+    println!("Loaded {} defs", counts.join(", "));
+    println!("{}", report);
+
+    // Missing translations are only reported, as they do not keep the game from running.
+    let strings = read_strings(asset_folder);
+    println!("{}", strings.find_missing_translations(&registry.def_names(&databases)));
+
+    if report.is_ok() {
+        0
+    } else {
+        1
+    }
+}
+
+fn read_strings(asset_folder: &str) -> LocalizedStrings {
+    let locale_tables = read_locale_tables(Path::new(asset_folder));
+    for (path, table) in locale_tables.iter() {
+        if let Some(message) = &table.parse_error {
+            println!("Failed to parse locale table '{}': {}", path, message);
+        }
+    }
+    LocalizedStrings::from_tables(locale_tables.iter().map(|(path, table)| (path.clone(), table)))
+}
+
+fn bake_defs(args: &[String]) -> i32 {
+    let asset_folder = args.first().map(String::as_str).unwrap_or("assets");
+    let app = headless_app(asset_folder);
+    let registry = app.world.resource::<DefTypeRegistry>();
+
+    let (databases, report) = load_defs_from_disk(
+        registry,
+        app.world.resource::<AssetServer>(),
+        &PathBuf::from(asset_folder),
+    );
+    // The game does not validate baked defs, so only defs without errors are baked.
+    if !report.is_ok() {
+        println!("{}", report);
+        println!("Fix the errors before baking the defs");
+        return 1;
+    }
+
+    let strings = read_strings(asset_folder);
+    match write_def_cache(registry, &databases, &strings, Path::new(asset_folder)) {
+        Ok(path) => {
+            println!("Baked the defs into {}", path.display());
+            0
+        }
+        Err(err) => {
+            eprintln!("Failed to bake the defs: {}", err);
+            1
+        }
+    }
+}
+
+fn diff_defs_command(args: &[String]) -> i32 {
+    let [old_folder, new_folder] = args else {
+        eprintln!("diff-defs takes the old and the new asset folder\n\n{}", USAGE);
+        return 2;
+    };
+
+    let (old, old_ok) = load_defs_to_diff(old_folder);
+    let (new, new_ok) = load_defs_to_diff(new_folder);
+    println!("{}", diff_defs(&old, &new));
+
+    // The fields of defs with errors are not compared, so the diff is not the whole story then.
+    if old_ok && new_ok {
+        0
+    } else {
+        1
+    }
+}
+
+/// Loads the defs of an asset folder, noting which of them failed to load.
+fn load_defs_to_diff(asset_folder: &str) -> (ContentVersion, bool) {
+    let app = headless_app(asset_folder);
+    let registry = app.world.resource::<DefTypeRegistry>();
+
+    let (resolved, databases, report) = load_resolved_defs_from_disk(
+        registry,
+        app.world.resource::<AssetServer>(),
+        &PathBuf::from(asset_folder),
+    );
+    if !report.is_ok() {
+        println!("{}: {}", asset_folder, report);
+    }
+
+    let loaded: HashSet<(&'static str, String)> = registry.def_names(&databases).into_iter().collect();
+    let failed = resolved
+        .iter()
+        .map(|def| (def.location.type_name, def.location.def_name.clone()))
+        .filter(|key| !loaded.contains(key))
+        .collect();
+    let version = ContentVersion {
+        defs: resolved,
+        failed,
+    };
+    (version, report.is_ok())
+}
+
+fn export_schema(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let output_folder = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .map(String::as_str)
+        .unwrap_or("assets");
+    let output_folder = Path::new(output_folder);
+
+    let app = headless_app("assets");
+    let registry = app.world.resource::<DefTypeRegistry>();
+
+    if check {
+        let outdated = find_outdated_schemas(registry, output_folder);
+        if outdated.is_empty() {
+            println!("Schemas are up to date");
+            return 0;
+        }
+        for path in outdated {
+            println!("Schema is out of date: {}", path.display());
+        }
+        println!("Run `monster-tamer export-schema` to update them");
+        return 1;
+    }
+
+    match write_schemas(registry, output_folder) {
+        Ok(()) => {
+            println!("Wrote schemas to {}", output_folder.display());
+            0
+        }
+        Err(err) => {
+            eprintln!("Failed to write schemas to {}: {}", output_folder.display(), err);
+            1
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::def_database::StableHasher;
+use crate::def_packs::PACKS_FOLDER;
+use crate::def_registry::{DefDatabases, DefTypeRegistry};
+use crate::def_schema::generate_schemas;
+use crate::json_asset_definition::{asset_folder, PendingAssetReferences, DEFS_FOLDER};
+use crate::localization::{LocalizedStrings, LOCALE_FOLDER};
+use crate::prelude::*;
+use bevy::ecs::system::CommandQueue;
+use serde::{Deserialize, Serialize};
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+/// The file the linked defs are baked into, relative to the assets folder.
+pub const DEF_CACHE_FILE: &str = "defs.cache";
+/// Bump when a linked def type changes, as only the raw def types are part of the content hash.
+const CACHE_FORMAT_VERSION: u32 = 5;
+/// The folders with the files the defs are built from, relative to the assets folder.
+const CONTENT_FOLDERS: &[&str] = &[DEFS_FOLDER, PACKS_FOLDER, LOCALE_FOLDER];
+
+/// The linked databases of all def types, along with a hash of everything they were built from.
+#[derive(Serialize, Deserialize)]
+struct DefCache {
+    /// Comes first, so it can be checked without reading the rest.
+    content_hash: u64,
+    /// The database of each def type, as `(type name, bytes)` pairs.
+    databases: Vec<(String, Vec<u8>)>,
+    strings: LocalizedStrings,
+}
+
+/// Hashes the content folders, along with the def types of this build, as either changes what the defs
+/// link into.
+pub fn content_hash(registry: &DefTypeRegistry, asset_folder: &Path) -> std::io::Result<u64> {
+    let mut hasher = StableHasher::default();
+    hasher.write(&CACHE_FORMAT_VERSION.to_le_bytes());
+    hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+    for (_, schema) in generate_schemas(registry) {
+        hasher.write(schema.as_bytes());
+    }
+
+    let mut files = Vec::new();
+    for folder in CONTENT_FOLDERS {
+        let dir = asset_folder.join(folder);
+        if dir.is_dir() {
+            find_files(&dir, &mut files)?;
+        }
+    }
+    // Sorted on the path relative to the assets, so the hash does not depend on where the game is installed.
+    let mut files: Vec<(String, PathBuf)> = files
+        .into_iter()
+        .map(|file| {
+            let path = file.strip_prefix(asset_folder).unwrap_or(&file).to_string_lossy().replace('\\', "/");
+            (path, file)
+        })
+        .collect();
+    files.sort();
+
+    for (path, file) in files {
+        let bytes = std::fs::read(&file)?;
+        hasher.write(path.as_bytes());
+        hasher.write(&(bytes.len() as u64).to_le_bytes());
+        hasher.write(&bytes);
+    }
+    Ok(hasher.finish())
+}
+
+fn find_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Bakes the databases into the def cache of the asset folder. They should have loaded without errors,
+/// as the game trusts the cache and skips validating the defs.
+pub fn write_def_cache(
+    registry: &DefTypeRegistry,
+    databases: &DefDatabases,
+    strings: &LocalizedStrings,
+    asset_folder: &Path,
+) -> Result<PathBuf> {
+    let cache = DefCache {
+        content_hash: content_hash(registry, asset_folder)?,
+        databases: registry.bake_databases(databases)?,
+        strings: strings.clone(),
+    };
+
+    let path = asset_folder.join(DEF_CACHE_FILE);
+    std::fs::write(&path, bincode::serialize(&cache)?)?;
+    Ok(path)
+}
+
+/// Reads the def cache of the asset folder, returning `None` when there is none or it is out of date.
+///
+/// The cache only holds the paths of the assets the defs refer to, so it cannot be read once one of them has
+/// gone missing since baking.
+pub fn read_def_cache(
+    registry: &DefTypeRegistry,
+    asset_server: &AssetServer,
+    asset_folder: &Path,
+) -> Result<Option<(DefDatabases, LocalizedStrings)>> {
+    let Ok(bytes) = std::fs::read(asset_folder.join(DEF_CACHE_FILE)) else {
+        return Ok(None);
+    };
+
+    let cached_hash: u64 = bincode::deserialize(&bytes)?;
+    if cached_hash != content_hash(registry, asset_folder)? {
+        return Ok(None);
+    }
+
+    let cache: DefCache = bincode::deserialize(&bytes)?;
+    let databases = registry
+        .unbake_databases(&cache.databases, asset_server)
+        .map_err(|message| anyhow!(message))?;
+    Ok(Some((databases, cache.strings)))
+}
+
+/// Uses the def cache when it is up to date, and loads the def files otherwise.
+///
+/// The defs of the cache are trusted, but the assets they refer to are still validated, as those are not
+/// part of the cache and a label can only be checked once its file has loaded.
+pub fn load_def_cache(world: &mut World) {
+    let cached = asset_folder(world.resource::<AssetServer>()).and_then(|asset_folder| {
+        world.resource_scope(|world, registry: Mut<DefTypeRegistry>| {
+            read_def_cache(&registry, world.resource::<AssetServer>(), &asset_folder)
+                .unwrap_or_else(|err| {
+                    warn!("Ignoring the def cache, as it could not be read: {}", err);
+                    None
+                })
+        })
+    });
+
+    let Some((mut databases, strings)) = cached else {
+        info!("No up to date def cache, loading the def files");
+        world.resource_mut::<NextState<GameState>>().set(GameState::LoadingDefs);
+        return;
+    };
+
+    info!("Loaded definitions from the def cache");
+    world.resource_scope(|world, registry: Mut<DefTypeRegistry>| {
+        let translations = strings.find_missing_translations(&registry.def_names(&databases));
+        if !translations.is_complete() {
+            warn!("{}", translations);
+        }
+        world.insert_resource(strings);
+        world.insert_resource(translations);
+        world.insert_resource(PendingAssetReferences::new(std::mem::take(&mut databases.asset_references)));
+
+        let mut queue = CommandQueue::default();
+        registry.insert_resources(&mut Commands::new(&mut queue, world), databases);
+        queue.apply(world);
+    });
+    world.resource_mut::<NextState<GameState>>().set(GameState::ValidatingAssets);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::headless_app;
+    use crate::json_asset_definition::load_defs_from_disk;
+    use crate::monsters::{MonsterDefinition, MonsterType};
+
+    /// Copies the def folders of the game to a fresh folder, so the cache can be written without touching the assets.
+    fn copy_assets(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("monster-tamer-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&folder);
+        for content_folder in CONTENT_FOLDERS {
+            let source = Path::new("assets").join(content_folder);
+            let mut files = Vec::new();
+            find_files(&source, &mut files).unwrap();
+            for file in files {
+                let target = folder.join(file.strip_prefix("assets").unwrap());
+                std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+                std::fs::copy(&file, &target).unwrap();
+            }
+        }
+        folder
+    }
+
+    #[test]
+    fn baked_defs_read_back_the_same() {
+        let folder = copy_assets("baked");
+        let app = headless_app("assets");
+        let registry = app.world.resource::<DefTypeRegistry>();
+        let asset_server = app.world.resource::<AssetServer>();
+        let (databases, report) = load_defs_from_disk(registry, asset_server, &folder);
+        assert!(report.is_ok(), "{}", report);
+
+        write_def_cache(registry, &databases, &LocalizedStrings::default(), &folder).unwrap();
+        let (cached, _) = read_def_cache(registry, asset_server, &folder).unwrap().unwrap();
+
+        assert_eq!(cached.asset_references.len(), databases.asset_references.len());
+        assert_eq!(registry.def_names(&cached), registry.def_names(&databases));
+        let fire = cached.get::<MonsterType>().unwrap().get_by_name("fire").unwrap();
+        assert_eq!(fire, databases.get::<MonsterType>().unwrap().get_by_name("fire").unwrap());
+        let block = cached.get::<MonsterDefinition>().unwrap().get_by_name("block").unwrap();
+        assert_eq!(block, databases.get::<MonsterDefinition>().unwrap().get_by_name("block").unwrap());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn defs_with_missing_assets_make_the_cache_unreadable() {
+        let app = headless_app("assets");
+        let registry = app.world.resource::<DefTypeRegistry>();
+        let asset_server = app.world.resource::<AssetServer>();
+        let (databases, _) = load_defs_from_disk(registry, asset_server, Path::new("assets"));
+        let mut block = databases.get::<MonsterDefinition>().unwrap().get_by_name("block").unwrap().clone();
+        block.model_path = "monsters/404/model.glb#Scene0".to_string();
+        let mut baked = registry.bake_databases(&databases).unwrap();
+        let (_, monsters) = baked
+            .iter_mut()
+            .find(|(type_name, _)| type_name == MonsterDefinition::TYPE_NAME)
+            .unwrap();
+        *monsters = bincode::serialize(&vec![(None::<String>, block)]).unwrap();
+
+        let Err(message) = registry.unbake_databases(&baked, asset_server) else {
+            panic!("expected the cache to be unreadable");
+        };
+        assert!(message.contains("asset 'monsters/404/model.glb#Scene0' does not exist"), "{}", message);
+    }
+
+    #[test]
+    fn changed_defs_make_the_cache_out_of_date() {
+        let folder = copy_assets("changed");
+        let app = headless_app("assets");
+        let registry = app.world.resource::<DefTypeRegistry>();
+        let asset_server = app.world.resource::<AssetServer>();
+        let (databases, _) = load_defs_from_disk(registry, asset_server, &folder);
+        write_def_cache(registry, &databases, &LocalizedStrings::default(), &folder).unwrap();
+
+        std::fs::write(
+            folder.join(DEFS_FOLDER).join("dunes.json"),
+            r#"{"type": "Biome", "min_height": 1}"#,
+        )
+        .unwrap();
+
+        assert!(read_def_cache(registry, asset_server, &folder).unwrap().is_none());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
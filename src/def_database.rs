@@ -1,9 +1,15 @@
+use crate::def_errors::DefFieldError;
 use crate::prelude::*;
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 
 pub trait Definition: Send + Sync + 'static {
+    /// The name of the def type, as used for the `type` tag in def files.
+    const TYPE_NAME: &'static str;
+
     fn get_def_name(&self) -> &str;
 }
 
@@ -12,10 +18,56 @@ pub struct DefDatabase<T>
 where
     T: Definition,
 {
-    by_name_map: HashMap<String, u64>,
+    // Ordered, so iteration is deterministic and sorted by def name.
+    by_name_map: BTreeMap<String, u64>,
     by_id_map: HashMap<u64, DefInfo<T>>,
 
-    next_id: u64,
+    indexes: HashMap<&'static str, Box<dyn AnyIndex<T>>>,
+}
+
+/// Type erased access to a [`SecondaryIndex`], so indexes with different key types can live in the same database.
+trait AnyIndex<T>: Send + Sync {
+    fn add(&mut self, id: u64, def: &T);
+    fn remove(&mut self, def: &T);
+    fn as_any(&self) -> &dyn Any;
+}
+
+type KeyExtractor<T, K> = Box<dyn Fn(&T) -> Vec<K> + Send + Sync>;
+
+struct SecondaryIndex<T, K> {
+    extract_keys: KeyExtractor<T, K>,
+    // The ids are keyed by def name, so lookups have the same ordering as the database itself.
+    entries: HashMap<K, BTreeMap<String, u64>>,
+}
+
+impl<T, K> AnyIndex<T> for SecondaryIndex<T, K>
+where
+    T: Definition,
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    fn add(&mut self, id: u64, def: &T) {
+        for key in (self.extract_keys)(def) {
+            self.entries
+                .entry(key)
+                .or_default()
+                .insert(def.get_def_name().to_string(), id);
+        }
+    }
+
+    fn remove(&mut self, def: &T) {
+        for key in (self.extract_keys)(def) {
+            if let Some(ids) = self.entries.get_mut(&key) {
+                ids.remove(def.get_def_name());
+                if ids.is_empty() {
+                    self.entries.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 pub struct DefInfo<T>
@@ -24,23 +76,41 @@ where
 {
     pub id: DefId<T>,
     pub definition: T,
+    /// The content pack the def was loaded from, if any.
+    pub pack_id: Option<String>,
 }
 
 impl<T> Definition for DefInfo<T>
 where
     T: Definition,
 {
+    const TYPE_NAME: &'static str = T::TYPE_NAME;
+
     fn get_def_name(&self) -> &str {
         self.definition.get_def_name()
     }
 }
 
-#[derive(Debug, Clone, Copy)]
 pub struct DefId<TDef> {
     id: u64,
     _phantom: PhantomData<TDef>,
 }
 
+// Implemented by hand, as deriving would require `TDef` to implement the traits as well.
+impl<TDef> Clone for DefId<TDef> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<TDef> Copy for DefId<TDef> {}
+
+impl<TDef> std::fmt::Debug for DefId<TDef> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DefId").field(&self.id).finish()
+    }
+}
+
 impl<TDef> DefId<TDef> {
     pub fn new(id: u64) -> Self {
         Self {
@@ -48,6 +118,19 @@ impl<TDef> DefId<TDef> {
             _phantom: PhantomData,
         }
     }
+
+    /// Derives the id from the def name, so the same def always gets the same id,
+    /// regardless of load order, reloads or which build of the game is running.
+    pub fn from_def_name(def_name: &str) -> Self {
+        let mut hasher = StableHasher::default();
+        hasher.write(def_name.as_bytes());
+        Self::new(hasher.finish())
+    }
+
+    /// The raw id value, suitable for persisting in save files or sending over the network.
+    pub fn raw(&self) -> u64 {
+        self.id
+    }
 }
 
 impl<TDef> PartialEq<Self> for DefId<TDef> {
@@ -64,18 +147,100 @@ impl<TDef> Hash for DefId<TDef> {
     }
 }
 
+// Stored as the raw id, so linked defs can be written to the def cache.
+impl<TDef> Serialize for DefId<TDef> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.id.serialize(serializer)
+    }
+}
+
+impl<'de, TDef> Deserialize<'de> for DefId<TDef> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(DefId::new)
+    }
+}
+
+/// 64-bit FNV-1a, for hashes that have to be the same between runs and builds of the game,
+/// which the std hashers do not guarantee.
+pub struct StableHasher {
+    hash: u64,
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        StableHasher { hash: FNV_OFFSET_BASIS }
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        const FNV_PRIME: u64 = 0x100000001b3;
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
 impl<T> DefDatabase<T>
 where
     T: Definition,
 {
     pub fn new() -> Self {
         Self {
-            by_name_map: HashMap::new(),
+            by_name_map: BTreeMap::new(),
             by_id_map: HashMap::new(),
-            next_id: 0,
+            indexes: HashMap::new(),
         }
     }
 
+    /// Registers a secondary index, which maps each key returned by `extract_keys` to the definitions
+    /// that produced it. The index is built from the current content, and kept up to date by
+    /// [`Self::insert`] and [`Self::replace`].
+    pub fn add_index<K, F>(&mut self, name: &'static str, extract_keys: F)
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+        F: Fn(&T) -> Vec<K> + Send + Sync + 'static,
+    {
+        let mut index = SecondaryIndex {
+            extract_keys: Box::new(extract_keys),
+            entries: HashMap::new(),
+        };
+        for def in self.by_id_map.values() {
+            index.add(def.id.id, &def.definition);
+        }
+        self.indexes.insert(name, Box::new(index));
+    }
+
+    /// Finds the definitions with the given key in the named index, ordered by def name.
+    ///
+    /// Panics if no index with the name and key type has been registered.
+    pub fn get_by_index<K>(&self, name: &str, key: &K) -> impl Iterator<Item = (DefId<T>, &T)> + '_
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+    {
+        let index = self
+            .indexes
+            .get(name)
+            .and_then(|index| index.as_any().downcast_ref::<SecondaryIndex<T, K>>())
+            .unwrap_or_else(|| panic!("Index '{}' with key type {} not found", name, std::any::type_name::<K>()));
+
+        index
+            .entries
+            .get(key)
+            .into_iter()
+            .flat_map(|ids| ids.values())
+            .map(|id| {
+                let def = &self.by_id_map[id];
+                (def.id, &def.definition)
+            })
+    }
+
     pub fn get_by_id(&self, id: &DefId<T>) -> Option<&T> {
         let def = self.by_id_map.get(&id.id)?;
         Some(&def.definition)
@@ -85,18 +250,71 @@ where
         self.by_name_map.get(name).map(|id| DefId::new(*id))
     }
 
-    pub fn insert(&mut self, value: T) {
-        self.next_id += 1;
+    /// Looks up a def referenced by name from the field at `pointer` of another def.
+    pub fn resolve_reference(&self, name: &str, pointer: impl Into<String>) -> Result<DefId<T>, DefFieldError> {
+        self.get_def_id(name).ok_or_else(|| DefFieldError::UnknownReference {
+            pointer: pointer.into(),
+            target_type: T::TYPE_NAME,
+            target_name: name.to_string(),
+        })
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&T> {
+        let id = self.by_name_map.get(name)?;
+        self.by_id_map.get(id).map(|def| &def.definition)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.by_name_map.contains_key(name)
+    }
+
+    /// Iterates all definitions, ordered by def name.
+    pub fn iter(&self) -> impl Iterator<Item = (DefId<T>, &T)> + '_ {
+        self.by_name_map.values().map(|id| {
+            let def = &self.by_id_map[id];
+            (def.id, &def.definition)
+        })
+    }
+
+    /// Iterates the definitions matching the predicate, ordered by def name.
+    pub fn filter<'a, P>(&'a self, mut predicate: P) -> impl Iterator<Item = (DefId<T>, &'a T)> + 'a
+    where
+        P: FnMut(&T) -> bool + 'a,
+    {
+        self.iter().filter(move |(_, def)| predicate(def))
+    }
+
+    pub fn insert(&mut self, value: T) -> Result<DefId<T>> {
+        let id = DefId::from_def_name(value.get_def_name());
+        self.insert_with_id(id, value)
+    }
+
+    fn insert_with_id(&mut self, id: DefId<T>, value: T) -> Result<DefId<T>> {
+        if let Some(existing) = self.by_id_map.get(&id.id) {
+            if existing.get_def_name() == value.get_def_name() {
+                return Err(anyhow!("Def '{}' already exists", value.get_def_name()));
+            }
+            return Err(anyhow!(
+                "Def id collision: '{}' and '{}' both hash to id {}",
+                existing.get_def_name(),
+                value.get_def_name(),
+                id.raw()
+            ));
+        }
+
+        for index in self.indexes.values_mut() {
+            index.add(id.id, &value);
+        }
+
         let key = value.get_def_name().to_string();
         let def_info = DefInfo {
-            id: DefId {
-                id: self.next_id,
-                _phantom: PhantomData,
-            },
+            id,
             definition: value,
+            pack_id: None,
         };
-        self.by_name_map.insert(key, def_info.id.id);
-        self.by_id_map.insert(def_info.id.id, def_info);
+        self.by_name_map.insert(key, id.id);
+        self.by_id_map.insert(id.id, def_info);
+        Ok(id)
     }
 
     pub fn replace(&mut self, value: T) {
@@ -105,21 +323,32 @@ where
             .get(value.get_def_name())
             .unwrap_or_else(|| panic!("Def {} not found for replacement", value.get_def_name()));
 
-        self.by_id_map.get_mut(existing_id).unwrap().definition = value;
+        let existing = self.by_id_map.get_mut(existing_id).unwrap();
+        for index in self.indexes.values_mut() {
+            index.remove(&existing.definition);
+            index.add(*existing_id, &value);
+        }
+        existing.definition = value;
+    }
+
+    /// Records the content pack a def was loaded from.
+    pub fn set_pack(&mut self, id: &DefId<T>, pack_id: &str) {
+        if let Some(def) = self.by_id_map.get_mut(&id.id) {
+            def.pack_id = Some(pack_id.to_string());
+        }
+    }
+
+    /// The content pack a def was loaded from, or `None` for defs that were not loaded from a pack.
+    pub fn get_pack(&self, id: &DefId<T>) -> Option<&str> {
+        self.by_id_map.get(&id.id)?.pack_id.as_deref()
     }
 
     pub fn len(&self) -> usize {
         self.by_id_map.len()
     }
-}
 
-impl<T: Definition> FromIterator<T> for DefDatabase<T> {
-    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let mut db = Self::new();
-        for item in iter {
-            db.insert(item);
-        }
-        db
+    pub fn is_empty(&self) -> bool {
+        self.by_id_map.is_empty()
     }
 }
 
@@ -133,6 +362,8 @@ mod tests {
     }
 
     impl Definition for SimpleTestDef {
+        const TYPE_NAME: &'static str = "SimpleTestDef";
+
         fn get_def_name(&self) -> &str {
             &self.name
         }
@@ -141,7 +372,7 @@ mod tests {
     #[test]
     fn insert_and_get() {
         let mut db = DefDatabase::<SimpleTestDef>::new();
-        db.insert(SimpleTestDef { name: "test".to_string(), value: 42 });
+        db.insert(SimpleTestDef { name: "test".to_string(), value: 42 }).unwrap();
         let id = db.get_def_id("test").unwrap();
         let def = db.get_by_id(&id).unwrap();
         assert_eq!(def.name, "test");
@@ -151,7 +382,7 @@ mod tests {
     #[test]
     fn replace() {
         let mut db = DefDatabase::<SimpleTestDef>::new();
-        db.insert(SimpleTestDef { name: "test".to_string(), value: 42 });
+        db.insert(SimpleTestDef { name: "test".to_string(), value: 42 }).unwrap();
         let id = db.get_def_id("test").unwrap();
         let def = db.get_by_id(&id).unwrap();
         assert_eq!(def.name, "test");
@@ -161,4 +392,115 @@ mod tests {
         assert_eq!(def.name, "test");
         assert_eq!(def.value, 43);
     }
+
+    #[test]
+    fn ids_are_independent_of_insertion_order() {
+        let mut first = DefDatabase::<SimpleTestDef>::new();
+        first.insert(SimpleTestDef { name: "a".to_string(), value: 1 }).unwrap();
+        first.insert(SimpleTestDef { name: "b".to_string(), value: 2 }).unwrap();
+
+        let mut second = DefDatabase::<SimpleTestDef>::new();
+        second.insert(SimpleTestDef { name: "b".to_string(), value: 2 }).unwrap();
+        second.insert(SimpleTestDef { name: "a".to_string(), value: 1 }).unwrap();
+
+        assert_eq!(first.get_def_id("a"), second.get_def_id("a"));
+        assert_eq!(first.get_def_id("b"), second.get_def_id("b"));
+        assert_ne!(first.get_def_id("a"), first.get_def_id("b"));
+    }
+
+    #[test]
+    fn ids_are_stable_between_builds() {
+        // If this changes, every persisted id is invalidated.
+        assert_eq!(DefId::<SimpleTestDef>::from_def_name("test").raw(), 0xf9e6e6ef197c2b25);
+    }
+
+    #[test]
+    fn id_collision_is_an_error() {
+        let mut db = DefDatabase::<SimpleTestDef>::new();
+        let id = db.insert(SimpleTestDef { name: "a".to_string(), value: 1 }).unwrap();
+        let result = db.insert_with_id(id, SimpleTestDef { name: "b".to_string(), value: 2 });
+        assert!(result.is_err());
+        assert_eq!(db.get_by_id(&id).unwrap().value, 1);
+        assert_eq!(db.get_def_id("b"), None);
+    }
+
+    #[test]
+    fn inserting_duplicate_name_is_an_error() {
+        let mut db = DefDatabase::<SimpleTestDef>::new();
+        db.insert(SimpleTestDef { name: "test".to_string(), value: 42 }).unwrap();
+        let result = db.insert(SimpleTestDef { name: "test".to_string(), value: 43 });
+        assert!(result.is_err());
+        assert_eq!(db.len(), 1);
+        let id = db.get_def_id("test").unwrap();
+        assert_eq!(db.get_by_id(&id).unwrap().value, 42);
+    }
+
+    #[test]
+    fn iterates_in_def_name_order() {
+        let mut db = DefDatabase::<SimpleTestDef>::new();
+        db.insert(SimpleTestDef { name: "c".to_string(), value: 3 }).unwrap();
+        db.insert(SimpleTestDef { name: "a".to_string(), value: 1 }).unwrap();
+        db.insert(SimpleTestDef { name: "b".to_string(), value: 2 }).unwrap();
+
+        let names: Vec<&str> = db.iter().map(|(_, def)| def.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+
+        for (id, def) in db.iter() {
+            assert_eq!(db.get_def_id(&def.name), Some(id));
+        }
+    }
+
+    #[test]
+    fn query_by_name_and_predicate() {
+        let mut db = DefDatabase::<SimpleTestDef>::new();
+        db.insert(SimpleTestDef { name: "small".to_string(), value: 1 }).unwrap();
+        db.insert(SimpleTestDef { name: "big".to_string(), value: 100 }).unwrap();
+        db.insert(SimpleTestDef { name: "huge".to_string(), value: 1000 }).unwrap();
+
+        assert!(db.contains("small"));
+        assert!(!db.contains("tiny"));
+        assert_eq!(db.get_by_name("big").unwrap().value, 100);
+        assert!(db.get_by_name("tiny").is_none());
+
+        let large: Vec<&str> = db.filter(|def| def.value >= 100).map(|(_, def)| def.name.as_str()).collect();
+        assert_eq!(large, vec!["big", "huge"]);
+    }
+
+    #[test]
+    fn secondary_index_lookup() {
+        let mut db = DefDatabase::<SimpleTestDef>::new();
+        db.insert(SimpleTestDef { name: "b".to_string(), value: 1 }).unwrap();
+        db.insert(SimpleTestDef { name: "a".to_string(), value: 1 }).unwrap();
+        db.add_index("by_value", |def: &SimpleTestDef| vec![def.value]);
+        db.insert(SimpleTestDef { name: "c".to_string(), value: 2 }).unwrap();
+
+        let ones: Vec<&str> = db.get_by_index("by_value", &1u32).map(|(_, def)| def.name.as_str()).collect();
+        assert_eq!(ones, vec!["a", "b"]);
+        let twos: Vec<&str> = db.get_by_index("by_value", &2u32).map(|(_, def)| def.name.as_str()).collect();
+        assert_eq!(twos, vec!["c"]);
+        assert_eq!(db.get_by_index("by_value", &3u32).count(), 0);
+    }
+
+    #[test]
+    fn secondary_index_is_updated_on_replace() {
+        let mut db = DefDatabase::<SimpleTestDef>::new();
+        db.add_index("by_value", |def: &SimpleTestDef| vec![def.value]);
+        db.insert(SimpleTestDef { name: "a".to_string(), value: 1 }).unwrap();
+        db.replace(SimpleTestDef { name: "a".to_string(), value: 2 });
+
+        assert_eq!(db.get_by_index("by_value", &1u32).count(), 0);
+        assert_eq!(db.get_by_index("by_value", &2u32).count(), 1);
+    }
+
+    #[test]
+    fn remembers_pack_of_def() {
+        let mut db = DefDatabase::<SimpleTestDef>::new();
+        let id = db.insert(SimpleTestDef { name: "a".to_string(), value: 1 }).unwrap();
+        assert_eq!(db.get_pack(&id), None);
+
+        db.set_pack(&id, "volcano");
+        db.replace(SimpleTestDef { name: "a".to_string(), value: 2 });
+
+        assert_eq!(db.get_pack(&id), Some("volcano"));
+    }
 }
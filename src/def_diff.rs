@@ -0,0 +1,345 @@
+use crate::def_registry::SourcedDef;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// How a def differs between two versions of the content.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefChange {
+    Added,
+    Removed,
+    Changed(Vec<FieldChange>),
+    /// The def is in the files of the version, but has errors. Its fields are not compared then, as a def
+    /// that only fails in one version would otherwise show up as added or removed.
+    FailedToLoad { in_old: bool, in_new: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefDiff {
+    pub type_name: &'static str,
+    pub def_name: String,
+    pub change: DefChange,
+}
+
+/// A field that was added, removed or changed, where `path` reads like `damage_scales[fire].damage_scale`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+/// The defs of one version of the content, as resolved from their files.
+pub struct ContentVersion {
+    pub defs: Vec<SourcedDef>,
+    /// The defs that failed to load, as `(type name, def name)` pairs.
+    pub failed: HashSet<(&'static str, String)>,
+}
+
+/// The differences between two versions of the content, sorted by def type and name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContentDiff {
+    pub defs: Vec<DefDiff>,
+}
+
+/// Compares the fields of the defs as resolved from their files, so references read as def names
+/// rather than ids.
+///
+/// Lists of objects that refer to a def, like the damage scales of a type or the learn set of a monster,
+/// are matched on that reference instead of their position, so reordering them is not a change.
+pub fn diff_defs(old_version: &ContentVersion, new_version: &ContentVersion) -> ContentDiff {
+    let by_key = |defs: &[SourcedDef]| -> BTreeMap<(&'static str, String), Map<String, Value>> {
+        defs.iter()
+            .map(|def| ((def.location.type_name, def.location.def_name.clone()), def.fields.clone()))
+            .collect()
+    };
+    let old = by_key(&old_version.defs);
+    let new = by_key(&new_version.defs);
+    let keys: BTreeSet<&(&'static str, String)> = old.keys().chain(new.keys()).collect();
+
+    let mut defs = Vec::new();
+    for key in keys {
+        let in_old = old_version.failed.contains(*key);
+        let in_new = new_version.failed.contains(*key);
+        let change = match (old.get(key), new.get(key)) {
+            _ if in_old || in_new => DefChange::FailedToLoad { in_old, in_new },
+            (Some(old_fields), Some(new_fields)) => {
+                let mut changes = Vec::new();
+                diff_objects("", old_fields, new_fields, &mut changes);
+                if changes.is_empty() {
+                    continue;
+                }
+                DefChange::Changed(changes)
+            }
+            (None, Some(_)) => DefChange::Added,
+            (Some(_), None) => DefChange::Removed,
+            (None, None) => unreachable!("the key comes from one of the versions"),
+        };
+        defs.push(DefDiff {
+            type_name: key.0,
+            def_name: key.1.clone(),
+            change,
+        });
+    }
+
+    ContentDiff { defs }
+}
+
+fn diff_objects(path: &str, old: &Map<String, Value>, new: &Map<String, Value>, changes: &mut Vec<FieldChange>) {
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for name in names {
+        let field_path = if path.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", path, name)
+        };
+        diff_values(field_path, old.get(name), new.get(name), changes);
+    }
+}
+
+fn diff_values(path: String, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<FieldChange>) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => diff_objects(&path, old, new, changes),
+        (Some(Value::Array(old_items)), Some(Value::Array(new_items))) if old_items != new_items => {
+            match reference_field(old_items, new_items) {
+                Some(field) => diff_keyed_lists(&path, field, old_items, new_items, changes),
+                None => changes.push(FieldChange {
+                    path,
+                    old: old.cloned(),
+                    new: new.cloned(),
+                }),
+            }
+        }
+        _ if old != new => changes.push(FieldChange {
+            path,
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+fn diff_keyed_lists(path: &str, field: &str, old: &[Value], new: &[Value], changes: &mut Vec<FieldChange>) {
+    let by_reference = |items: &[Value]| -> BTreeMap<String, Value> {
+        items
+            .iter()
+            .map(|item| (item[field].as_str().unwrap_or_default().to_string(), item.clone()))
+            .collect()
+    };
+    let old = by_reference(old);
+    let new = by_reference(new);
+    let references: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for reference in references {
+        diff_values(
+            format!("{}[{}]", path, reference),
+            old.get(reference),
+            new.get(reference),
+            changes,
+        );
+    }
+}
+
+/// The field the items of both lists can be matched on: a def reference, like `target_type_def_name`,
+/// that every item has and no two items of a list share.
+fn reference_field<'a>(old: &'a [Value], new: &[Value]) -> Option<&'a str> {
+    let first = old.first()?.as_object()?;
+    first
+        .keys()
+        .filter(|name| name.ends_with("def_name") || name.ends_with("_def"))
+        .map(String::as_str)
+        .find(|name| is_unique_reference(old, name) && is_unique_reference(new, name))
+}
+
+fn is_unique_reference(items: &[Value], field: &str) -> bool {
+    let mut seen = HashSet::new();
+    items
+        .iter()
+        .all(|item| item.get(field).and_then(Value::as_str).is_some_and(|reference| seen.insert(reference)))
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", self.path, old, new),
+            (None, Some(new)) => write!(f, "{}: added {}", self.path, new),
+            (Some(old), None) => write!(f, "{}: removed {}", self.path, old),
+            (None, None) => write!(f, "{}: unchanged", self.path),
+        }
+    }
+}
+
+impl Display for ContentDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.defs.is_empty() {
+            return write!(f, "No definitions changed");
+        }
+
+        let mut counts = [0; 4];
+        for def in self.defs.iter() {
+            let (marker, index) = match def.change {
+                DefChange::Added => ('+', 0),
+                DefChange::Removed => ('-', 1),
+                DefChange::Changed(_) => ('~', 2),
+                DefChange::FailedToLoad { .. } => ('!', 3),
+            };
+            counts[index] += 1;
+            write!(f, "{} {} '{}'", marker, def.type_name, def.def_name)?;
+            match &def.change {
+                DefChange::Changed(fields) => {
+                    writeln!(f)?;
+                    for field in fields {
+                        writeln!(f, "    {}", field)?;
+                    }
+                }
+                DefChange::FailedToLoad { in_old, in_new } => {
+                    let version = match (in_old, in_new) {
+                        (true, true) => "both versions",
+                        (true, false) => "the old version",
+                        _ => "the new version",
+                    };
+                    writeln!(f, " failed to load in {}", version)?;
+                }
+                _ => writeln!(f)?,
+            }
+        }
+        write!(
+            f,
+            "{} added, {} removed, {} changed, {} failed to load",
+            counts[0], counts[1], counts[2], counts[3]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::def_errors::DefLocation;
+    use crate::def_packs::BASE_PACK_ID;
+    use serde_json::json;
+
+    fn def(type_name: &'static str, def_name: &str, fields: Value) -> SourcedDef {
+        let Value::Object(fields) = fields else {
+            panic!("expected an object");
+        };
+        SourcedDef {
+            location: DefLocation {
+                pack_id: BASE_PACK_ID.to_string(),
+                source_path: "defs/test.json".to_string(),
+                index: Some(0),
+                type_name,
+                def_name: def_name.to_string(),
+            },
+            fields,
+        }
+    }
+
+    fn version(defs: Vec<SourcedDef>) -> ContentVersion {
+        ContentVersion {
+            defs,
+            failed: HashSet::new(),
+        }
+    }
+
+    fn change(path: &str, old: Option<Value>, new: Option<Value>) -> FieldChange {
+        FieldChange {
+            path: path.to_string(),
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn added_and_removed_defs_are_listed() {
+        let old = vec![def("Biome", "dunes", json!({}))];
+        let new = vec![def("Biome", "plains", json!({}))];
+
+        let diff = diff_defs(&version(old), &version(new));
+
+        let changes: Vec<(&str, &DefChange)> =
+            diff.defs.iter().map(|def| (def.def_name.as_str(), &def.change)).collect();
+        assert_eq!(changes, vec![("dunes", &DefChange::Removed), ("plains", &DefChange::Added)]);
+    }
+
+    #[test]
+    fn changed_fields_show_both_values() {
+        let old = vec![def("MonsterMove", "tackle", json!({"base_damage": 40, "base_accuracy": 1}))];
+        let new = vec![def("MonsterMove", "tackle", json!({"base_damage": 45, "base_accuracy": 1}))];
+
+        let diff = diff_defs(&version(old), &version(new));
+
+        assert_eq!(
+            diff.defs[0].change,
+            DefChange::Changed(vec![change("base_damage", Some(json!(40)), Some(json!(45)))])
+        );
+    }
+
+    #[test]
+    fn type_chart_entries_are_matched_on_their_target() {
+        let old = vec![def(
+            "MonsterType",
+            "water",
+            json!({"damage_scales": [
+                {"target_type_def_name": "fire", "damage_scale": 2},
+                {"target_type_def_name": "water", "damage_scale": 0.5}
+            ]}),
+        )];
+        let new = vec![def(
+            "MonsterType",
+            "water",
+            json!({"damage_scales": [
+                {"target_type_def_name": "grass", "damage_scale": 0.5},
+                {"target_type_def_name": "water", "damage_scale": 0.5},
+                {"target_type_def_name": "fire", "damage_scale": 1.5}
+            ]}),
+        )];
+
+        let diff = diff_defs(&version(old), &version(new));
+
+        assert_eq!(
+            diff.defs[0].change,
+            DefChange::Changed(vec![
+                change("damage_scales[fire].damage_scale", Some(json!(2)), Some(json!(1.5))),
+                change(
+                    "damage_scales[grass]",
+                    None,
+                    Some(json!({"target_type_def_name": "grass", "damage_scale": 0.5}))
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn reordered_learn_sets_are_unchanged() {
+        let learn_set = |moves: &[&str]| -> Value {
+            moves
+                .iter()
+                .map(|name| json!({"monster_move_def_name": name, "conditions": [{"Level": 0}]}))
+                .collect()
+        };
+        let old = vec![def("Monster", "block", json!({"move_learn_set": learn_set(&["tackle", "ember"])}))];
+        let new = vec![def("Monster", "block", json!({"move_learn_set": learn_set(&["ember", "tackle"])}))];
+
+        assert_eq!(diff_defs(&version(old), &version(new)), ContentDiff::default());
+    }
+
+    #[test]
+    fn defs_that_failed_to_load_are_not_removed() {
+        let old = vec![def("Biome", "dunes", json!({"min_height": 0}))];
+        let mut new = version(vec![def("Biome", "dunes", json!({"min_height": -1}))]);
+        new.failed.insert(("Biome", "dunes".to_string()));
+
+        let diff = diff_defs(&version(old), &new);
+
+        assert_eq!(
+            diff.defs[0].change,
+            DefChange::FailedToLoad {
+                in_old: false,
+                in_new: true
+            }
+        );
+        assert_eq!(
+            diff.to_string(),
+            "! Biome 'dunes' failed to load in the new version\n0 added, 0 removed, 0 changed, 1 failed to load"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use crate::prelude::*;
+use std::fmt::{Display, Formatter};
+
+/// Where a def came from, used to point content authors at the offending file and entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefLocation {
+    pub pack_id: String,
+    pub source_path: String,
+    /// Index of the def in the `defs` list of its source file, or `None` when the file is a single bare def.
+    pub index: Option<usize>,
+    pub type_name: &'static str,
+    pub def_name: String,
+}
+
+impl DefLocation {
+    /// Turns a pointer relative to the def into a JSON pointer relative to the source file.
+    pub fn pointer(&self, field_pointer: &str) -> String {
+        match self.index {
+            Some(index) => format!("/defs/{}{}", index, field_pointer),
+            None => field_pointer.to_string(),
+        }
+    }
+}
+
+impl Display for DefLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(
+                f,
+                "{} def '{}' (pack '{}', '{}', index {})",
+                self.type_name, self.def_name, self.pack_id, self.source_path, index
+            ),
+            None => write!(
+                f,
+                "{} def '{}' (pack '{}', '{}')",
+                self.type_name, self.def_name, self.pack_id, self.source_path
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefLoadError {
+    /// The source file could not be parsed at all.
+    Parse { source_path: String, message: String },
+    /// A def has a `type` that is not a registered def type.
+    UnknownType {
+        source_path: String,
+        pointer: String,
+        type_name: String,
+    },
+    /// A def refers to another def that does not exist.
+    UnknownReference {
+        location: DefLocation,
+        pointer: String,
+        target_type: &'static str,
+        target_name: String,
+    },
+    /// A def has the same type and name as a def loaded earlier, without being marked as override.
+    DuplicateName {
+        location: DefLocation,
+        previous_pack_id: String,
+        previous_source_path: String,
+    },
+    /// A def has a different name than a def loaded earlier, but both names hash to the same id.
+    IdCollision {
+        location: DefLocation,
+        previous_def_name: String,
+    },
+    /// A field has a value that is not allowed.
+    InvalidValue {
+        location: DefLocation,
+        pointer: String,
+        message: String,
+    },
+    /// A def refers to an asset that does not exist, or does not have the sub-asset named by its label.
+    InvalidAsset {
+        location: DefLocation,
+        pointer: String,
+        path: String,
+        message: String,
+    },
+    /// Following the `parent` of a def leads back to a def that was already visited.
+    InheritanceCycle { location: DefLocation, cycle: Vec<String> },
+    /// A content pack could not be loaded, so its defs are left out.
+    Pack { pack_id: String, message: String },
+    /// A def that existed before a hot reload is gone, which would leave dangling ids behind.
+    RemovedOnReload { type_name: &'static str, def_name: String },
+}
+
+impl Display for DefLoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefLoadError::Parse { source_path, message } => {
+                write!(f, "Failed to parse '{}': {}", source_path, message)
+            }
+            DefLoadError::UnknownType {
+                source_path,
+                pointer,
+                type_name,
+            } => write!(f, "'{}' at {}: unknown def type '{}'", source_path, pointer, type_name),
+            DefLoadError::UnknownReference {
+                location,
+                pointer,
+                target_type,
+                target_name,
+            } => write!(
+                f,
+                "{} at {}: {} def '{}' not found",
+                location, pointer, target_type, target_name
+            ),
+            DefLoadError::DuplicateName {
+                location,
+                previous_pack_id,
+                previous_source_path,
+            } => write!(
+                f,
+                "{}: already defined by pack '{}' in '{}'. Set \"override\": true to replace it intentionally",
+                location, previous_pack_id, previous_source_path
+            ),
+            DefLoadError::IdCollision {
+                location,
+                previous_def_name,
+            } => write!(
+                f,
+                "{}: has the same id as {} def '{}'. Rename one of them",
+                location, location.type_name, previous_def_name
+            ),
+            DefLoadError::InvalidValue {
+                location,
+                pointer,
+                message,
+            } => write!(f, "{} at {}: {}", location, pointer, message),
+            DefLoadError::InvalidAsset {
+                location,
+                pointer,
+                path,
+                message,
+            } => write!(f, "{} at {}: asset '{}' {}", location, pointer, path, message),
+            DefLoadError::InheritanceCycle { location, cycle } => write!(
+                f,
+                "{} at {}: inherits from itself through {}",
+                location,
+                location.pointer("/parent"),
+                cycle.join(" -> ")
+            ),
+            DefLoadError::Pack { pack_id, message } => write!(f, "Pack '{}' {}", pack_id, message),
+            DefLoadError::RemovedOnReload { type_name, def_name } => write!(
+                f,
+                "{} def '{}' was removed, which requires a restart",
+                type_name, def_name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DefLoadError {}
+
+/// An error in a single field of a def, with a JSON pointer relative to the def itself.
+///
+/// The loader turns it into a [`DefLoadError`] once it knows where the def came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefFieldError {
+    UnknownReference {
+        pointer: String,
+        target_type: &'static str,
+        target_name: String,
+    },
+    InvalidValue { pointer: String, message: String },
+    InvalidAsset {
+        pointer: String,
+        path: String,
+        message: String,
+    },
+}
+
+impl DefFieldError {
+    pub fn into_load_error(self, location: &DefLocation) -> DefLoadError {
+        match self {
+            DefFieldError::UnknownReference {
+                pointer,
+                target_type,
+                target_name,
+            } => DefLoadError::UnknownReference {
+                location: location.clone(),
+                pointer: location.pointer(&pointer),
+                target_type,
+                target_name,
+            },
+            DefFieldError::InvalidValue { pointer, message } => DefLoadError::InvalidValue {
+                location: location.clone(),
+                pointer: location.pointer(&pointer),
+                message,
+            },
+            DefFieldError::InvalidAsset { pointer, path, message } => DefLoadError::InvalidAsset {
+                location: location.clone(),
+                pointer: location.pointer(&pointer),
+                path,
+                message,
+            },
+        }
+    }
+}
+
+/// Checks that `value` is at least `min`, and at most `max` when given.
+///
+/// The bounds should be the same constants as used in the `#[schemars(range(...))]` attribute of the field,
+/// so the schema and the loader agree.
+pub fn check_range(pointer: &str, value: f32, min: f32, max: Option<f32>) -> Result<(), DefFieldError> {
+    // Written so NaN fails the check as well
+    let in_range = value >= min && max.is_none_or(|max| value <= max);
+    if in_range {
+        return Ok(());
+    }
+
+    let message = match max {
+        Some(max) => format!("{} is not between {} and {}", value, min, max),
+        None => format!("{} is less than the minimum of {}", value, min),
+    };
+    Err(DefFieldError::InvalidValue {
+        pointer: pointer.to_string(),
+        message,
+    })
+}
+
+/// Checks that an optional lower bound is not above an optional upper bound, like `min_height` and `max_height`.
+pub fn check_bounds_order(
+    min_pointer: &str,
+    min: Option<f32>,
+    max_pointer: &str,
+    max: Option<f32>,
+) -> Result<(), DefFieldError> {
+    match (min, max) {
+        (Some(min), Some(max)) if min > max => Err(DefFieldError::InvalidValue {
+            pointer: min_pointer.to_string(),
+            message: format!("{} is greater than {} at {}", min, max, max_pointer),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// The outcome of the last attempt at loading the defs.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct DefLoadReport {
+    pub errors: Vec<DefLoadError>,
+}
+
+impl DefLoadReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl Display for DefLoadReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.errors.is_empty() {
+            return write!(f, "All definitions loaded without errors");
+        }
+
+        write!(f, "{} definition error(s):", self.errors.len())?;
+        for error in self.errors.iter() {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
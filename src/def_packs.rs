@@ -0,0 +1,427 @@
+use crate::def_errors::DefLoadError;
+use crate::def_types::DefsRoot;
+use crate::prelude::*;
+use bevy::asset::{AssetLoader, Error, LoadContext, LoadedAsset};
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// The folder in the assets with a folder per content pack.
+pub const PACKS_FOLDER: &str = "packs";
+/// The file in the root of a pack folder that declares the pack.
+pub const PACK_MANIFEST_FILE: &str = "pack.manifest.json";
+/// The id of the pack formed by the defs folder of the game itself. Every other pack is loaded after it.
+pub const BASE_PACK_ID: &str = "base";
+
+/// Declares a content pack, a folder of defs that is loaded on top of the base game, like an expansion.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, TypeUuid)]
+#[uuid = "6f0d3a57-2c1e-4b8a-9e2f-91c4d07b5a13"]
+pub struct PackManifest {
+    /// Unique id of the pack, used by other packs to refer to it.
+    pub id: String,
+    pub version: String,
+    /// Packs that must be present, and are loaded before this pack.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Packs that are loaded before this pack when they are present, so this pack can override their defs.
+    #[serde(default)]
+    pub load_after: Vec<String>,
+    /// Set by the loader when the manifest could not be parsed.
+    #[serde(skip)]
+    pub parse_error: Option<String>,
+}
+
+impl PackManifest {
+    fn base() -> Self {
+        PackManifest {
+            id: BASE_PACK_ID.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            depends_on: Vec::new(),
+            load_after: Vec::new(),
+            parse_error: None,
+        }
+    }
+
+    /// Parses a manifest. A broken manifest still produces a pack, named after its folder,
+    /// so the error ends up in the load report instead of stalling the loading state.
+    pub fn parse(bytes: &[u8], manifest_path: &str) -> Self {
+        serde_json::from_slice(bytes).unwrap_or_else(|err| PackManifest {
+            id: pack_folder(manifest_path).unwrap_or(manifest_path).to_string(),
+            version: String::new(),
+            depends_on: Vec::new(),
+            load_after: Vec::new(),
+            parse_error: Some(err.to_string()),
+        })
+    }
+}
+
+pub struct PackManifestLoader;
+
+impl AssetLoader for PackManifestLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let path = load_context.path().to_string_lossy().replace('\\', "/");
+            load_context.set_default_asset(LoadedAsset::new(PackManifest::parse(bytes, &path)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        // Picked over the json def loader, as the most specific extension wins.
+        &["manifest.json"]
+    }
+}
+
+/// The pack folder a file belongs to, like `packs/volcano` for `packs/volcano/monsters/emberpup.json`.
+pub fn pack_folder(path: &str) -> Option<&str> {
+    let rest = path.strip_prefix(PACKS_FOLDER)?.strip_prefix('/')?;
+    let folder_len = PACKS_FOLDER.len() + 1 + rest.find('/')?;
+    Some(&path[..folder_len])
+}
+
+/// A content pack, along with its def files as `(path, root)` pairs.
+pub struct Pack<'a> {
+    pub manifest: PackManifest,
+    pub files: Vec<(String, &'a DefsRoot)>,
+}
+
+/// A def file, along with the pack it belongs to.
+pub struct DefSource<'a> {
+    pub pack_id: String,
+    pub path: String,
+    pub root: &'a DefsRoot,
+}
+
+/// Groups the def files into packs, and puts the packs in load order.
+///
+/// `base_files` are the files of the defs folder. `manifests` and `pack_files` are the manifests and def files
+/// found below [`PACKS_FOLDER`], as `(path, ...)` pairs, and are grouped by the pack folder they are in.
+pub fn collect_packs<'a>(
+    base_files: Vec<(String, &'a DefsRoot)>,
+    manifests: Vec<(String, PackManifest)>,
+    pack_files: Vec<(String, &'a DefsRoot)>,
+    errors: &mut Vec<DefLoadError>,
+) -> Vec<DefSource<'a>> {
+    let mut packs_by_folder: BTreeMap<String, Pack<'a>> = BTreeMap::new();
+    for (path, manifest) in manifests {
+        let Some(folder) = pack_folder(&path) else {
+            continue;
+        };
+        packs_by_folder.insert(
+            folder.to_string(),
+            Pack {
+                manifest,
+                files: Vec::new(),
+            },
+        );
+    }
+
+    let mut folders_without_manifest = BTreeSet::new();
+    for (path, root) in pack_files {
+        let folder = pack_folder(&path).unwrap_or(PACKS_FOLDER);
+        match packs_by_folder.get_mut(folder) {
+            Some(pack) => pack.files.push((path, root)),
+            None => {
+                folders_without_manifest.insert(folder.to_string());
+            }
+        }
+    }
+    for folder in folders_without_manifest {
+        errors.push(DefLoadError::Pack {
+            pack_id: folder,
+            message: format!("has no {}, so its defs are skipped", PACK_MANIFEST_FILE),
+        });
+    }
+
+    let mut packs = vec![Pack {
+        manifest: PackManifest::base(),
+        files: base_files,
+    }];
+    packs.extend(packs_by_folder.into_values());
+
+    order_packs(packs, errors)
+        .into_iter()
+        .flat_map(|mut pack| {
+            // Files are applied in path order, so "later" has a stable meaning for overrides within a pack.
+            pack.files.sort_by(|a, b| a.0.cmp(&b.0));
+            info!(
+                "Loading pack '{}' {} with {} def files",
+                pack.manifest.id,
+                pack.manifest.version,
+                pack.files.len()
+            );
+            let pack_id = pack.manifest.id;
+            pack.files.into_iter().map(move |(path, root)| DefSource {
+                pack_id: pack_id.clone(),
+                path,
+                root,
+            })
+        })
+        .collect()
+}
+
+/// Orders the packs so every pack comes after the packs it depends on or loads after, and after the base pack.
+/// Packs that are not ordered by that are loaded in order of their id.
+///
+/// Packs that cannot be loaded, because their manifest is broken, a dependency is missing, or they are part
+/// of a cycle, are left out and reported.
+fn order_packs<'a>(packs: Vec<Pack<'a>>, errors: &mut Vec<DefLoadError>) -> Vec<Pack<'a>> {
+    let mut remaining: BTreeMap<String, Pack<'a>> = BTreeMap::new();
+    for pack in packs {
+        let pack_id = pack.manifest.id.clone();
+        if let Some(message) = &pack.manifest.parse_error {
+            errors.push(DefLoadError::Pack {
+                pack_id,
+                message: format!("failed to parse {}: {}", PACK_MANIFEST_FILE, message),
+            });
+        } else if remaining.contains_key(&pack_id) {
+            errors.push(DefLoadError::Pack {
+                pack_id,
+                message: "is declared by more than one pack folder".to_string(),
+            });
+        } else {
+            remaining.insert(pack_id, pack);
+        }
+    }
+
+    // Dropping a pack can break the packs depending on it, so repeat until nothing changes.
+    loop {
+        let broken: Vec<(String, String)> = remaining
+            .values()
+            .filter_map(|pack| {
+                let missing = pack.manifest.depends_on.iter().find(|dep| !remaining.contains_key(*dep))?;
+                Some((pack.manifest.id.clone(), missing.clone()))
+            })
+            .collect();
+        if broken.is_empty() {
+            break;
+        }
+        for (pack_id, missing) in broken {
+            remaining.remove(&pack_id);
+            errors.push(DefLoadError::Pack {
+                pack_id,
+                message: format!("depends on pack '{}', which is missing or failed to load", missing),
+            });
+        }
+    }
+
+    let mut ordered: Vec<Pack<'a>> = Vec::new();
+    let mut loaded: HashSet<String> = HashSet::new();
+    loop {
+        let ready = remaining
+            .values()
+            .find(|pack| {
+                let manifest = &pack.manifest;
+                let after_base = manifest.id == BASE_PACK_ID || loaded.contains(BASE_PACK_ID);
+                let predecessors_loaded = manifest
+                    .depends_on
+                    .iter()
+                    .chain(manifest.load_after.iter().filter(|id| remaining.contains_key(*id)))
+                    .all(|id| loaded.contains(id));
+                after_base && predecessors_loaded
+            })
+            .map(|pack| pack.manifest.id.clone());
+        let Some(pack_id) = ready else {
+            break;
+        };
+
+        let pack = remaining.remove(&pack_id).unwrap();
+        loaded.insert(pack_id);
+        ordered.push(pack);
+    }
+
+    // The packs left are either part of a cycle, or wait for a pack that is.
+    for pack in remaining.values() {
+        let pack_id = &pack.manifest.id;
+        let predecessors = unordered_predecessors(&pack.manifest, &remaining);
+        let cycle = predecessors.iter().find_map(|predecessor| {
+            let path = find_load_order_path(predecessor, pack_id, &remaining, &mut HashSet::new())?;
+            Some([vec![pack_id.clone()], path].concat())
+        });
+        let message = match cycle {
+            Some(cycle) => format!("is part of a load order cycle through {}", cycle.join(" -> ")),
+            None => format!(
+                "has to load after pack '{}', which could not be loaded because of a load order cycle",
+                predecessors.first().map(String::as_str).unwrap_or_default()
+            ),
+        };
+        errors.push(DefLoadError::Pack {
+            pack_id: pack_id.clone(),
+            message,
+        });
+    }
+
+    ordered
+}
+
+/// The packs that could not be ordered which `manifest` has to load after.
+fn unordered_predecessors(manifest: &PackManifest, remaining: &BTreeMap<String, Pack>) -> Vec<String> {
+    let base = (manifest.id != BASE_PACK_ID).then(|| BASE_PACK_ID.to_string());
+    manifest
+        .depends_on
+        .iter()
+        .chain(manifest.load_after.iter())
+        .cloned()
+        .chain(base)
+        .filter(|id| remaining.contains_key(id))
+        .collect()
+}
+
+/// The ids of the packs on a load order path from `from` to `to`, including both.
+fn find_load_order_path(
+    from: &str,
+    to: &str,
+    remaining: &BTreeMap<String, Pack>,
+    visited: &mut HashSet<String>,
+) -> Option<Vec<String>> {
+    if from == to {
+        return Some(vec![to.to_string()]);
+    }
+    if !visited.insert(from.to_string()) {
+        return None;
+    }
+
+    let pack = remaining.get(from)?;
+    unordered_predecessors(&pack.manifest, remaining).iter().find_map(|predecessor| {
+        let path = find_load_order_path(predecessor, to, remaining, visited)?;
+        Some([vec![from.to_string()], path].concat())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(id: &str, depends_on: &[&str], load_after: &[&str]) -> PackManifest {
+        PackManifest {
+            id: id.to_string(),
+            version: "1.0.0".to_string(),
+            depends_on: depends_on.iter().map(|id| id.to_string()).collect(),
+            load_after: load_after.iter().map(|id| id.to_string()).collect(),
+            parse_error: None,
+        }
+    }
+
+    fn pack(manifest: PackManifest) -> Pack<'static> {
+        Pack {
+            manifest,
+            files: Vec::new(),
+        }
+    }
+
+    fn ordered_ids(packs: Vec<Pack>, errors: &mut Vec<DefLoadError>) -> Vec<String> {
+        order_packs(packs, errors)
+            .into_iter()
+            .map(|pack| pack.manifest.id)
+            .collect()
+    }
+
+    #[test]
+    fn packs_load_after_their_dependencies() {
+        let packs = vec![
+            pack(manifest("volcano_plus", &["volcano"], &[])),
+            pack(manifest("volcano", &[], &["rebalance"])),
+            pack(manifest("rebalance", &[], &[])),
+            pack(PackManifest::base()),
+        ];
+        let mut errors = Vec::new();
+
+        let ids = ordered_ids(packs, &mut errors);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(ids, vec!["base", "rebalance", "volcano", "volcano_plus"]);
+    }
+
+    #[test]
+    fn missing_dependencies_skip_the_pack_and_its_dependents() {
+        let packs = vec![
+            pack(PackManifest::base()),
+            pack(manifest("volcano", &["tundra"], &[])),
+            pack(manifest("volcano_plus", &["volcano"], &[])),
+            pack(manifest("desert", &[], &["tundra"])),
+        ];
+        let mut errors = Vec::new();
+
+        let ids = ordered_ids(packs, &mut errors);
+
+        assert_eq!(ids, vec!["base", "desert"]);
+        let skipped: Vec<&str> = errors
+            .iter()
+            .filter_map(|err| match err {
+                DefLoadError::Pack { pack_id, .. } => Some(pack_id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(skipped, vec!["volcano", "volcano_plus"]);
+    }
+
+    #[test]
+    fn load_order_cycles_are_reported() {
+        let packs = vec![
+            pack(PackManifest::base()),
+            pack(manifest("a", &["b"], &[])),
+            pack(manifest("b", &[], &["a"])),
+            pack(manifest("c", &["a"], &[])),
+        ];
+        let mut errors = Vec::new();
+
+        let ids = ordered_ids(packs, &mut errors);
+
+        assert_eq!(ids, vec!["base"]);
+        let messages: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Pack 'a' is part of a load order cycle through a -> b -> a",
+                "Pack 'b' is part of a load order cycle through b -> a -> b",
+                "Pack 'c' has to load after pack 'a', which could not be loaded because of a load order cycle",
+            ]
+        );
+    }
+
+    #[test]
+    fn files_are_grouped_by_pack_folder() {
+        let root = DefsRoot {
+            defs: Vec::new(),
+            is_single_def: false,
+            parse_error: None,
+        };
+        let mut errors = Vec::new();
+
+        let sources = collect_packs(
+            vec![("defs/monsters.json".to_string(), &root)],
+            vec![("packs/volcano/pack.manifest.json".to_string(), manifest("volcano", &[], &[]))],
+            vec![
+                ("packs/volcano/monsters/emberpup.json".to_string(), &root),
+                ("packs/unknown/monsters/frostpup.json".to_string(), &root),
+            ],
+            &mut errors,
+        );
+
+        let files: Vec<(&str, &str)> = sources
+            .iter()
+            .map(|source| (source.pack_id.as_str(), source.path.as_str()))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                ("base", "defs/monsters.json"),
+                ("volcano", "packs/volcano/monsters/emberpup.json")
+            ]
+        );
+        assert!(matches!(&errors[..], [DefLoadError::Pack { pack_id, .. }] if pack_id == "packs/unknown"));
+    }
+
+    #[test]
+    fn pack_folder_is_the_first_folder_below_packs() {
+        assert_eq!(pack_folder("packs/volcano/monsters/emberpup.json"), Some("packs/volcano"));
+        assert_eq!(pack_folder("packs/volcano/pack.manifest.json"), Some("packs/volcano"));
+        assert_eq!(pack_folder("packs/stray.json"), None);
+        assert_eq!(pack_folder("defs/monsters.json"), None);
+    }
+}
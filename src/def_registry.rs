@@ -0,0 +1,675 @@
+use crate::def_cache::DEF_CACHE_FILE;
+use crate::def_errors::{DefFieldError, DefLoadError, DefLocation};
+use crate::def_schema::{def_type_schema, type_tag_schema};
+use crate::json_asset_definition::{asset_folder, ReloadedDef};
+use crate::prelude::*;
+use bevy::asset::Asset;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{RootSchema, Schema};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// A def type as written in def files, which is linked into its [`Definition`] once all defs are loaded.
+///
+/// Register it with [`RegisterDefType::register_def_type`], so the loader and the schema pick it up.
+pub trait RawDefinition: DeserializeOwned + JsonSchema + Send + Sync + 'static {
+    /// Serializable, so the linked defs can be baked into the def cache.
+    type Definition: Definition + Clone + PartialEq + Serialize + DeserializeOwned;
+
+    /// Turns the raw def into its definition, resolving the names of other defs through `context`.
+    ///
+    /// The ids of all defs are known before anything is linked, so a def can refer to any def of any type,
+    /// including its own type and itself.
+    fn link(&self, context: &LinkContext) -> Result<Self::Definition, DefFieldError>;
+
+    /// Checks the linked defs of the type against each other, for what no def can check on its own, like
+    /// cycles between defs. Returns the names of the defs that break a rule, which then fail to load as if
+    /// they had failed to link.
+    fn check_linked(_defs: &[&Self::Definition]) -> Vec<(String, DefFieldError)> {
+        Vec::new()
+    }
+
+    /// Adds the secondary indexes of the def type to a freshly built database.
+    fn add_indexes(_database: &mut DefDatabase<Self::Definition>) {}
+
+    /// Loads the assets of a def read from the def cache, which only holds their paths.
+    ///
+    /// Loaded through [`LinkContext::load_asset`], so they are validated like the assets of linked defs.
+    fn load_assets(_def: &mut Self::Definition, _context: &LinkContext) -> Result<(), DefFieldError> {
+        Ok(())
+    }
+}
+
+/// What a [`RawDefinition`] has access to while it is linked.
+pub struct LinkContext<'a> {
+    asset_server: &'a AssetServer,
+    /// The assets folder on disk, or `None` when assets are not loaded from files.
+    asset_folder: Option<&'a Path>,
+    ids: &'a AllocatedIds,
+    /// The assets loaded by the def that is being linked.
+    loaded_assets: RefCell<Vec<LinkedAsset>>,
+}
+
+struct LinkedAsset {
+    pointer: String,
+    path: String,
+    handle: HandleUntyped,
+    is_loaded: fn(&World, &HandleUntyped) -> bool,
+}
+
+impl<'a> LinkContext<'a> {
+    /// Loads an asset referenced by path from the field at `pointer`, like `monsters/001/model.glb#Scene0`.
+    ///
+    /// Fails when the file does not exist. The label can only be checked once the file has loaded,
+    /// which the loader does through the returned [`AssetReference`]s.
+    pub fn load_asset<T: Asset>(&self, path: &str, pointer: impl Into<String>) -> Result<Handle<T>, DefFieldError> {
+        let pointer = pointer.into();
+        let file = path.split_once('#').map_or(path, |(file, _)| file);
+        if let Some(asset_folder) = self.asset_folder {
+            if !asset_folder.join(file).is_file() {
+                return Err(DefFieldError::InvalidAsset {
+                    pointer,
+                    path: path.to_string(),
+                    message: "does not exist".to_string(),
+                });
+            }
+        }
+
+        let handle: Handle<T> = self.asset_server.load(path);
+        self.loaded_assets.borrow_mut().push(LinkedAsset {
+            pointer,
+            path: path.to_string(),
+            handle: handle.clone_untyped(),
+            is_loaded: is_asset_loaded::<T>,
+        });
+        Ok(handle)
+    }
+
+    /// Looks up a def referenced by name from the field at `pointer`.
+    ///
+    /// Panics if `T` is not a registered def type.
+    pub fn resolve<T: Definition>(&self, name: &str, pointer: impl Into<String>) -> Result<DefId<T>, DefFieldError> {
+        let names = self
+            .ids
+            .names
+            .get(T::TYPE_NAME)
+            .unwrap_or_else(|| panic!("{} is not a registered def type", T::TYPE_NAME));
+
+        if !names.contains(name) {
+            return Err(DefFieldError::UnknownReference {
+                pointer: pointer.into(),
+                target_type: T::TYPE_NAME,
+                target_name: name.to_string(),
+            });
+        }
+        if self.ids.has_failed(T::TYPE_NAME, name) {
+            return Err(DefFieldError::InvalidValue {
+                pointer: pointer.into(),
+                message: format!("refers to {} def '{}', which failed to load", T::TYPE_NAME, name),
+            });
+        }
+
+        Ok(DefId::from_def_name(name))
+    }
+
+    /// Takes the assets loaded since the last call, as references from the def at `location`.
+    fn take_asset_references(&self, location: &DefLocation) -> Vec<AssetReference> {
+        self.loaded_assets
+            .take()
+            .into_iter()
+            .map(|asset| AssetReference {
+                location: location.clone(),
+                pointer: location.pointer(&asset.pointer),
+                path: asset.path,
+                handle: asset.handle,
+                is_loaded: asset.is_loaded,
+            })
+            .collect()
+    }
+}
+
+fn is_asset_loaded<T: Asset>(world: &World, handle: &HandleUntyped) -> bool {
+    world
+        .get_resource::<Assets<T>>()
+        .is_some_and(|assets| assets.contains(handle.id()))
+}
+
+/// An asset a def refers to. Whether its label names a sub-asset is only known once the file has loaded.
+pub struct AssetReference {
+    pub location: DefLocation,
+    /// Relative to the source file, like the pointers of [`DefLoadError`]s.
+    pub pointer: String,
+    pub path: String,
+    pub handle: HandleUntyped,
+    is_loaded: fn(&World, &HandleUntyped) -> bool,
+}
+
+impl AssetReference {
+    /// Whether the asset, or the sub-asset named by the label, is in its asset storage.
+    pub fn is_loaded(&self, world: &World) -> bool {
+        (self.is_loaded)(world, &self.handle)
+    }
+}
+
+/// The def names of every type, collected before linking so references resolve regardless of the order
+/// defs are linked in. Ids are derived from the names, so this is all it takes to allocate them.
+#[derive(Default)]
+struct AllocatedIds {
+    names: HashMap<&'static str, HashSet<String>>,
+    /// Defs that failed to link, and are left out of the databases.
+    failed: HashMap<&'static str, HashSet<String>>,
+}
+
+impl AllocatedIds {
+    fn has_failed(&self, type_name: &str, def_name: &str) -> bool {
+        self.failed.get(type_name).is_some_and(|failed| failed.contains(def_name))
+    }
+}
+
+/// A def with its parents merged in, along with where it was loaded from.
+pub struct SourcedDef {
+    pub location: DefLocation,
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The databases of all def types, before they are inserted as resources.
+#[derive(Default)]
+pub struct DefDatabases {
+    databases: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    /// The assets the defs in the databases refer to.
+    pub asset_references: Vec<AssetReference>,
+}
+
+impl DefDatabases {
+    pub fn get<T: Definition>(&self) -> Option<&DefDatabase<T>> {
+        self.databases.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    fn insert<T: Definition>(&mut self, database: DefDatabase<T>) {
+        self.databases.insert(TypeId::of::<T>(), Box::new(database));
+    }
+
+    fn remove<T: Definition>(&mut self) -> Option<DefDatabase<T>> {
+        let database = self.databases.remove(&TypeId::of::<T>())?;
+        database.downcast::<DefDatabase<T>>().ok().map(|database| *database)
+    }
+}
+
+/// Type erased access to a registered [`RawDefinition`], so the loader can handle all def types the same way.
+trait DefTypeRegistration: Send + Sync {
+    fn type_name(&self) -> &'static str;
+    fn variant_schema(&self, gen: &mut SchemaGenerator) -> Schema;
+    fn type_schema(&self) -> RootSchema;
+    fn parse(&self, defs: &[SourcedDef], errors: &mut Vec<DefLoadError>) -> Box<dyn LinkDefs>;
+    fn count(&self, databases: &DefDatabases) -> usize;
+    fn def_names(&self, databases: &DefDatabases) -> Vec<String>;
+    fn bake(&self, databases: &DefDatabases) -> bincode::Result<Vec<u8>>;
+    fn unbake(
+        &self,
+        bytes: &[u8],
+        asset_server: &AssetServer,
+        databases: &mut DefDatabases,
+    ) -> Result<(), String>;
+    fn insert_resource(&self, commands: &mut Commands, databases: &mut DefDatabases);
+    fn find_removed_defs(&self, world: &World, reloaded: &DefDatabases, errors: &mut Vec<DefLoadError>);
+    fn apply_reloaded_defs(&self, world: &mut World, reloaded: &DefDatabases, changed: &mut Vec<ReloadedDef>);
+}
+
+struct Registration<R>(PhantomData<fn() -> R>);
+
+impl<R: RawDefinition> DefTypeRegistration for Registration<R> {
+    fn type_name(&self) -> &'static str {
+        R::Definition::TYPE_NAME
+    }
+
+    fn variant_schema(&self, gen: &mut SchemaGenerator) -> Schema {
+        let mut schema = R::json_schema(gen).into_object();
+        let object = schema.object();
+        // The tag goes first, like it does for a derived internally tagged enum.
+        let mut properties = schemars::Map::new();
+        properties.insert("type".to_string(), type_tag_schema(self.type_name()));
+        properties.extend(std::mem::take(&mut object.properties));
+        object.properties = properties;
+        object.required.insert("type".to_string());
+        Schema::Object(schema)
+    }
+
+    fn type_schema(&self) -> RootSchema {
+        def_type_schema::<R>(self.type_name())
+    }
+
+    fn parse(&self, defs: &[SourcedDef], errors: &mut Vec<DefLoadError>) -> Box<dyn LinkDefs> {
+        let mut raw_defs = Vec::new();
+        for d in defs.iter().filter(|d| d.location.type_name == self.type_name()) {
+            let mut value = d.fields.clone();
+            value.insert("def_name".to_string(), d.location.def_name.clone().into());
+            match serde_json::from_value::<R>(serde_json::Value::Object(value)) {
+                Ok(raw) => raw_defs.push((d.location.clone(), raw)),
+                Err(err) => errors.push(DefLoadError::InvalidValue {
+                    location: d.location.clone(),
+                    pointer: d.location.pointer(""),
+                    message: err.to_string(),
+                }),
+            }
+        }
+
+        Box::new(ParsedDefs::<R> {
+            raw_defs,
+            linked: Vec::new(),
+        })
+    }
+
+    fn count(&self, databases: &DefDatabases) -> usize {
+        databases.get::<R::Definition>().map_or(0, DefDatabase::len)
+    }
+
+    fn def_names(&self, databases: &DefDatabases) -> Vec<String> {
+        databases
+            .get::<R::Definition>()
+            .map(|database| database.iter().map(|(_, def)| def.get_def_name().to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    fn bake(&self, databases: &DefDatabases) -> bincode::Result<Vec<u8>> {
+        let defs: Vec<(Option<&str>, &R::Definition)> = databases
+            .get::<R::Definition>()
+            .into_iter()
+            .flat_map(|database| database.iter().map(|(id, def)| (database.get_pack(&id), def)))
+            .collect();
+        bincode::serialize(&defs)
+    }
+
+    fn unbake(
+        &self,
+        bytes: &[u8],
+        asset_server: &AssetServer,
+        databases: &mut DefDatabases,
+    ) -> Result<(), String> {
+        let defs: Vec<(Option<String>, R::Definition)> = bincode::deserialize(bytes)
+            .map_err(|err| format!("{} defs are unreadable: {}", self.type_name(), err))?;
+
+        let asset_folder = asset_folder(asset_server);
+        let context = LinkContext {
+            asset_server,
+            asset_folder: asset_folder.as_deref(),
+            ids: &AllocatedIds::default(),
+            loaded_assets: RefCell::new(Vec::new()),
+        };
+        let mut database = DefDatabase::new();
+        for (pack_id, mut def) in defs {
+            // The cache does not know which file a def came from, so its errors point at the cache.
+            let location = DefLocation {
+                pack_id: pack_id.clone().unwrap_or_default(),
+                source_path: DEF_CACHE_FILE.to_string(),
+                index: None,
+                type_name: self.type_name(),
+                def_name: def.get_def_name().to_string(),
+            };
+            // A missing asset fails the def, and with it the defs referring to it, which only linking reports.
+            R::load_assets(&mut def, &context).map_err(|err| err.into_load_error(&location).to_string())?;
+            databases.asset_references.extend(context.take_asset_references(&location));
+            // The defs were in a database when they were baked, so this only fails for a corrupted cache.
+            let id = database
+                .insert(def)
+                .map_err(|err| format!("{} defs do not fit in a database: {}", self.type_name(), err))?;
+            if let Some(pack_id) = pack_id {
+                database.set_pack(&id, &pack_id);
+            }
+        }
+        R::add_indexes(&mut database);
+
+        databases.insert(database);
+        Ok(())
+    }
+
+    fn insert_resource(&self, commands: &mut Commands, databases: &mut DefDatabases) {
+        if let Some(database) = databases.remove::<R::Definition>() {
+            commands.insert_resource(database);
+        }
+    }
+
+    fn find_removed_defs(&self, world: &World, reloaded: &DefDatabases, errors: &mut Vec<DefLoadError>) {
+        if let (Some(current), Some(reloaded)) = (
+            world.get_resource::<DefDatabase<R::Definition>>(),
+            reloaded.get::<R::Definition>(),
+        ) {
+            find_removed_defs(current, reloaded, errors);
+        }
+    }
+
+    fn apply_reloaded_defs(&self, world: &mut World, reloaded: &DefDatabases, changed: &mut Vec<ReloadedDef>) {
+        if let (Some(mut current), Some(reloaded)) = (
+            world.get_resource_mut::<DefDatabase<R::Definition>>(),
+            reloaded.get::<R::Definition>(),
+        ) {
+            apply_reloaded_defs(&mut current, reloaded, changed);
+        }
+    }
+}
+
+/// Type erased access to the parsed defs of one type, while they are being linked.
+trait LinkDefs {
+    fn def_names(&self) -> Vec<&str>;
+    /// Links the defs that have not failed yet, keeping the results and returning the defs that failed.
+    fn link(&mut self, context: &LinkContext) -> Vec<(DefLocation, DefFieldError)>;
+    /// Moves the defs linked by the last pass into a database.
+    fn build_database(self: Box<Self>, databases: &mut DefDatabases, errors: &mut Vec<DefLoadError>);
+}
+
+struct ParsedDefs<R: RawDefinition> {
+    raw_defs: Vec<(DefLocation, R)>,
+    /// The defs linked by the last pass, by their index in `raw_defs`, along with the assets they refer to.
+    linked: Vec<(usize, R::Definition, Vec<AssetReference>)>,
+}
+
+impl<R: RawDefinition> LinkDefs for ParsedDefs<R> {
+    fn def_names(&self) -> Vec<&str> {
+        self.raw_defs
+            .iter()
+            .map(|(location, _)| location.def_name.as_str())
+            .collect()
+    }
+
+    fn link(&mut self, context: &LinkContext) -> Vec<(DefLocation, DefFieldError)> {
+        self.linked.clear();
+        let mut failed = Vec::new();
+        for (i, (location, raw)) in self.raw_defs.iter().enumerate() {
+            if context.ids.has_failed(location.type_name, &location.def_name) {
+                continue;
+            }
+            let linked = raw.link(context);
+            let assets = context.take_asset_references(location);
+            match linked {
+                Ok(def) => self.linked.push((i, def, assets)),
+                Err(err) => failed.push((location.clone(), err)),
+            }
+        }
+
+        let defs: Vec<&R::Definition> = self.linked.iter().map(|(_, def, _)| def).collect();
+        for (def_name, err) in R::check_linked(&defs) {
+            let Some(position) = self.linked.iter().position(|(_, def, _)| def.get_def_name() == def_name) else {
+                continue;
+            };
+            let (i, _, _) = self.linked.remove(position);
+            failed.push((self.raw_defs[i].0.clone(), err));
+        }
+        failed
+    }
+
+    fn build_database(self: Box<Self>, databases: &mut DefDatabases, errors: &mut Vec<DefLoadError>) {
+        let mut database = DefDatabase::new();
+        for (i, def, assets) in self.linked {
+            let location = &self.raw_defs[i].0;
+            let id = DefId::from_def_name(def.get_def_name());
+            match database.insert(def) {
+                Ok(id) => {
+                    database.set_pack(&id, &location.pack_id);
+                    databases.asset_references.extend(assets);
+                }
+                // Inserting can only fail on an id collision, as duplicate names have already been filtered out.
+                Err(_) => errors.push(DefLoadError::IdCollision {
+                    location: location.clone(),
+                    previous_def_name: database
+                        .get_by_id(&id)
+                        .map(|previous| previous.get_def_name().to_string())
+                        .unwrap_or_default(),
+                }),
+            }
+        }
+        R::add_indexes(&mut database);
+
+        databases.insert(database);
+    }
+}
+
+/// The def types the loader knows about. Filled through [`RegisterDefType::register_def_type`].
+#[derive(Resource, Default)]
+pub struct DefTypeRegistry {
+    types: Vec<Box<dyn DefTypeRegistration>>,
+}
+
+impl DefTypeRegistry {
+    pub fn register<R: RawDefinition>(&mut self) {
+        let type_name = R::Definition::TYPE_NAME;
+        if self.find_type_name(type_name).is_some() {
+            panic!("Def type {} is registered twice", type_name);
+        }
+        self.types.push(Box::new(Registration::<R>(PhantomData)));
+    }
+
+    /// Looks up a type name read from a def file, returning `None` for types that are not registered.
+    pub fn find_type_name(&self, type_name: &str) -> Option<&'static str> {
+        self.types
+            .iter()
+            .map(|registration| registration.type_name())
+            .find(|name| *name == type_name)
+    }
+
+    /// The names of all registered types, sorted so they do not depend on the order plugins are added in.
+    pub fn type_names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.types.iter().map(|registration| registration.type_name()).collect();
+        names.sort();
+        names
+    }
+
+    fn sorted_types(&self) -> Vec<&dyn DefTypeRegistration> {
+        let mut types: Vec<&dyn DefTypeRegistration> = self.types.iter().map(|registration| registration.as_ref()).collect();
+        types.sort_by_key(|registration| registration.type_name());
+        types
+    }
+
+    /// Links the defs of every registered type into their databases.
+    ///
+    /// All ids are allocated before linking, so defs can refer to each other in any order. A def that fails
+    /// to link is left out, which breaks the defs referring to it in turn, so linking is repeated until
+    /// no more defs fail. That way no database ends up with an id of a def that is not in its database.
+    pub fn build_databases(
+        &self,
+        defs: &[SourcedDef],
+        asset_server: &AssetServer,
+        errors: &mut Vec<DefLoadError>,
+    ) -> DefDatabases {
+        let mut parsed: Vec<(&dyn DefTypeRegistration, Box<dyn LinkDefs>)> = self
+            .sorted_types()
+            .into_iter()
+            .map(|registration| (registration, registration.parse(defs, errors)))
+            .collect();
+
+        let asset_folder = asset_folder(asset_server);
+        let mut ids = AllocatedIds::default();
+        for (registration, defs) in parsed.iter() {
+            let names = defs.def_names().into_iter().map(str::to_string).collect();
+            ids.names.insert(registration.type_name(), names);
+        }
+
+        loop {
+            let context = LinkContext {
+                asset_server,
+                asset_folder: asset_folder.as_deref(),
+                ids: &ids,
+                loaded_assets: RefCell::new(Vec::new()),
+            };
+            let failed: Vec<(DefLocation, DefFieldError)> =
+                parsed.iter_mut().flat_map(|(_, defs)| defs.link(&context)).collect();
+            if failed.is_empty() {
+                break;
+            }
+
+            for (location, err) in failed {
+                ids.failed
+                    .entry(location.type_name)
+                    .or_default()
+                    .insert(location.def_name.clone());
+                errors.push(err.into_load_error(&location));
+            }
+        }
+
+        let mut databases = DefDatabases::default();
+        for (registration, defs) in parsed {
+            defs.build_database(&mut databases, errors);
+            info!(
+                "Loaded {} {} defs",
+                registration.count(&databases),
+                registration.type_name()
+            );
+        }
+        databases
+    }
+
+    /// The number of defs of each type, ordered by type name.
+    pub fn counts(&self, databases: &DefDatabases) -> Vec<(&'static str, usize)> {
+        self.sorted_types()
+            .into_iter()
+            .map(|registration| (registration.type_name(), registration.count(databases)))
+            .collect()
+    }
+
+    /// The type and def names of all defs, ordered by type name and then def name.
+    pub fn def_names(&self, databases: &DefDatabases) -> Vec<(&'static str, String)> {
+        self.sorted_types()
+            .into_iter()
+            .flat_map(|registration| {
+                registration
+                    .def_names(databases)
+                    .into_iter()
+                    .map(|def_name| (registration.type_name(), def_name))
+            })
+            .collect()
+    }
+
+    /// Serializes the database of every type, as `(type name, bytes)` pairs.
+    pub fn bake_databases(&self, databases: &DefDatabases) -> bincode::Result<Vec<(String, Vec<u8>)>> {
+        self.sorted_types()
+            .into_iter()
+            .map(|registration| Ok((registration.type_name().to_string(), registration.bake(databases)?)))
+            .collect()
+    }
+
+    /// Reads back the databases written by [`Self::bake_databases`], loading the assets of the defs.
+    ///
+    /// Fails when a registered type is missing, so a cache from before a type was added is not used, and when
+    /// an asset no longer exists. The other assets are left to validate in [`DefDatabases::asset_references`].
+    pub fn unbake_databases(
+        &self,
+        baked: &[(String, Vec<u8>)],
+        asset_server: &AssetServer,
+    ) -> Result<DefDatabases, String> {
+        let mut databases = DefDatabases::default();
+        for registration in self.sorted_types() {
+            let (_, bytes) = baked
+                .iter()
+                .find(|(type_name, _)| type_name == registration.type_name())
+                .ok_or_else(|| format!("{} defs are missing", registration.type_name()))?;
+            registration.unbake(bytes, asset_server, &mut databases)?;
+        }
+        Ok(databases)
+    }
+
+    /// Moves the databases into resources, so systems can use them as `Res<DefDatabase<T>>`.
+    pub fn insert_resources(&self, commands: &mut Commands, mut databases: DefDatabases) {
+        for registration in self.types.iter() {
+            registration.insert_resource(commands, &mut databases);
+        }
+    }
+
+    /// Checks that reloading does not remove any def from the current databases.
+    pub fn find_removed_defs(&self, world: &World, reloaded: &DefDatabases, errors: &mut Vec<DefLoadError>) {
+        for registration in self.sorted_types() {
+            registration.find_removed_defs(world, reloaded, errors);
+        }
+    }
+
+    /// Applies the reloaded defs to the current databases, returning the defs that changed.
+    pub fn apply_reloaded_defs(&self, world: &mut World, reloaded: &DefDatabases) -> Vec<ReloadedDef> {
+        let mut changed = Vec::new();
+        for registration in self.sorted_types() {
+            registration.apply_reloaded_defs(world, reloaded, &mut changed);
+        }
+        changed
+    }
+
+    /// The schemas of all types as they appear in a `defs` list, tagged with their `type`.
+    pub fn variant_schemas(&self, gen: &mut SchemaGenerator) -> Vec<Schema> {
+        self.sorted_types()
+            .into_iter()
+            .map(|registration| registration.variant_schema(gen))
+            .collect()
+    }
+
+    /// The schemas of the individual types, by type name.
+    pub fn type_schemas(&self) -> Vec<(&'static str, RootSchema)> {
+        self.sorted_types()
+            .into_iter()
+            .map(|registration| (registration.type_name(), registration.type_schema()))
+            .collect()
+    }
+}
+
+pub trait RegisterDefType {
+    /// Makes the def type available to def files, the loader and the schema.
+    fn register_def_type<R: RawDefinition>(&mut self) -> &mut Self;
+}
+
+impl RegisterDefType for App {
+    fn register_def_type<R: RawDefinition>(&mut self) -> &mut Self {
+        self.init_resource::<DefTypeRegistry>();
+        self.world.resource_mut::<DefTypeRegistry>().register::<R>();
+        self
+    }
+}
+
+/// Removing a def would leave dangling `DefId`s in anything holding on to it, so it needs a restart.
+fn find_removed_defs<T: Definition>(
+    current: &DefDatabase<T>,
+    reloaded: &DefDatabase<T>,
+    errors: &mut Vec<DefLoadError>,
+) {
+    for (_, def) in current.iter() {
+        if !reloaded.contains(def.get_def_name()) {
+            errors.push(DefLoadError::RemovedOnReload {
+                type_name: T::TYPE_NAME,
+                def_name: def.get_def_name().to_string(),
+            });
+        }
+    }
+}
+
+fn apply_reloaded_defs<T: Definition + Clone + PartialEq>(
+    current: &mut DefDatabase<T>,
+    reloaded: &DefDatabase<T>,
+    changed: &mut Vec<ReloadedDef>,
+) {
+    for (id, def) in reloaded.iter() {
+        let is_changed = match current.get_by_name(def.get_def_name()) {
+            Some(existing) if existing == def => false,
+            Some(_) => {
+                current.replace(def.clone());
+                true
+            }
+            None => {
+                // Ids are derived from the def name, so they will match the ids the reloaded defs were linked with.
+                current
+                    .insert(def.clone())
+                    .expect("Reloaded def did not fit in the database it was validated against");
+                true
+            }
+        };
+        // A def can move to another pack without changing, like when an override is removed.
+        if let Some(pack_id) = reloaded.get_pack(&id) {
+            current.set_pack(&id, pack_id);
+        }
+
+        if is_changed {
+            changed.push(ReloadedDef {
+                type_name: T::TYPE_NAME,
+                def_name: def.get_def_name().to_string(),
+            });
+        }
+    }
+}
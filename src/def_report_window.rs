@@ -0,0 +1,35 @@
+use crate::def_errors::DefLoadReport;
+use crate::prelude::*;
+use bevy_editor_pls::editor_window::{EditorWindow, EditorWindowContext};
+use bevy_editor_pls::egui;
+
+/// Editor window listing the errors from the last def load.
+pub struct DefLoadReportWindow;
+
+impl EditorWindow for DefLoadReportWindow {
+    type State = ();
+
+    const NAME: &'static str = "Def Load Report";
+
+    fn ui(world: &mut World, _cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let Some(report) = world.get_resource::<DefLoadReport>() else {
+            ui.label("Defs have not been loaded yet");
+            return;
+        };
+
+        if report.is_ok() {
+            ui.label("All definitions loaded without errors");
+            return;
+        }
+
+        ui.colored_label(
+            egui::Color32::RED,
+            format!("{} definition error(s)", report.errors.len()),
+        );
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for error in report.errors.iter() {
+                ui.label(error.to_string());
+            }
+        });
+    }
+}
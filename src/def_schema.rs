@@ -0,0 +1,149 @@
+use crate::def_registry::DefTypeRegistry;
+use schemars::gen::SchemaSettings;
+use schemars::schema::{ArrayValidation, InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use schemars::JsonSchema;
+use std::path::{Path, PathBuf};
+
+/// The schema for a whole def file, relative to the output folder.
+pub const ROOT_SCHEMA_FILE: &str = "def_schema.json";
+/// The folder with a schema per def type, relative to the output folder.
+pub const TYPE_SCHEMA_FOLDER: &str = "def_schema";
+/// The name the schema of an entry in the `defs` list is stored under in the root schema.
+const DEF_ENTRY_SCHEMA_NAME: &str = "DefEntry";
+
+/// Generates all schemas for the registered def types, as pairs of a path relative to the output folder and
+/// the file content.
+pub fn generate_schemas(registry: &DefTypeRegistry) -> Vec<(PathBuf, String)> {
+    let mut schemas = vec![(PathBuf::from(ROOT_SCHEMA_FILE), to_json(&root_schema(registry)))];
+
+    for (type_name, schema) in registry.type_schemas() {
+        schemas.push((
+            Path::new(TYPE_SCHEMA_FOLDER).join(format!("{}.json", type_name)),
+            to_json(&schema),
+        ));
+    }
+
+    schemas
+}
+
+// The fields every def entry can have, on top of the fields of its type.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct DefEntryFields {
+    /// Set to patch a def with the same type and name from an earlier file, replacing the fields set on this def.
+    #[serde(default, rename = "override", skip_serializing_if = "std::ops::Not::not")]
+    is_override: bool,
+    /// The name of a def of the same type to inherit all fields from that are not set on this def.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<String>,
+    /// Set for templates that are only meant to be inherited from. They are left out of the databases.
+    #[serde(default, rename = "abstract", skip_serializing_if = "std::ops::Not::not")]
+    is_abstract: bool,
+    /// List fields that are appended to the list of the parent or the overridden def, instead of replacing it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    append: Vec<String>,
+}
+
+/// The schema for a whole def file, with a `defs` list that can hold any registered def type.
+///
+/// Built by hand, as the def types are only known at runtime, but laid out like a derived schema for
+/// a `DefsRoot` holding an internally tagged enum of the def types.
+fn root_schema(registry: &DefTypeRegistry) -> RootSchema {
+    let mut gen = SchemaSettings::draft07().into_generator();
+
+    // Reserve the spot first, so the entry comes before the types it refers to.
+    gen.definitions_mut()
+        .insert(DEF_ENTRY_SCHEMA_NAME.to_string(), Schema::Bool(false));
+    let variants = registry.variant_schemas(&mut gen);
+    let mut entry = DefEntryFields::json_schema(&mut gen).into_object();
+    entry.subschemas().one_of = Some(variants);
+    gen.definitions_mut()
+        .insert(DEF_ENTRY_SCHEMA_NAME.to_string(), Schema::Object(entry));
+
+    let defs = SchemaObject {
+        instance_type: Some(SingleOrVec::Single(Box::new(InstanceType::Array))),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(SingleOrVec::Single(Box::new(Schema::new_ref(format!(
+                "#/definitions/{}",
+                DEF_ENTRY_SCHEMA_NAME
+            ))))),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    let mut root = SchemaObject {
+        instance_type: Some(SingleOrVec::Single(Box::new(InstanceType::Object))),
+        ..Default::default()
+    };
+    root.metadata().title = Some("DefsRoot".to_string());
+    root.object().required.insert("defs".to_string());
+    root.object().properties.insert("defs".to_string(), Schema::Object(defs));
+
+    RootSchema {
+        meta_schema: gen.settings().meta_schema.clone(),
+        schema: root,
+        definitions: gen.take_definitions(),
+    }
+}
+
+/// The schema of the `type` tag that selects a def type.
+pub fn type_tag_schema(type_name: &str) -> Schema {
+    Schema::Object(SchemaObject {
+        instance_type: Some(SingleOrVec::Single(Box::new(InstanceType::String))),
+        enum_values: Some(vec![type_name.into()]),
+        ..Default::default()
+    })
+}
+
+/// The schema for a single def of one type, including the `type` tag that selects it.
+pub fn def_type_schema<T: JsonSchema>(type_name: &str) -> RootSchema {
+    let mut schema = schemars::schema_for!(T);
+
+    let object = schema.schema.object();
+    object.properties.insert("type".to_string(), type_tag_schema(type_name));
+    object.required.insert("type".to_string());
+
+    schema
+}
+
+fn to_json(schema: &RootSchema) -> String {
+    serde_json::to_string_pretty(schema).expect("Failed to serialize schema to json")
+}
+
+pub fn write_schemas(registry: &DefTypeRegistry, output_folder: &Path) -> std::io::Result<()> {
+    for (path, content) in generate_schemas(registry) {
+        let path = output_folder.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+/// Returns the schema files in the output folder that are missing or do not match the def types.
+pub fn find_outdated_schemas(registry: &DefTypeRegistry, output_folder: &Path) -> Vec<PathBuf> {
+    generate_schemas(registry)
+        .into_iter()
+        .map(|(path, content)| (output_folder.join(path), content))
+        .filter(|(path, content)| std::fs::read_to_string(path).ok().as_ref() != Some(content))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::headless_app;
+
+    #[test]
+    fn committed_schemas_are_up_to_date() {
+        let app = headless_app("assets");
+        let outdated = find_outdated_schemas(app.world.resource::<DefTypeRegistry>(), Path::new("assets"));
+        assert!(
+            outdated.is_empty(),
+            "Schemas are out of date, run `cargo run -- export-schema` to update them: {:?}",
+            outdated
+        );
+    }
+}
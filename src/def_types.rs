@@ -1,20 +1,106 @@
-use crate::monsters::{MonsterRawDefinition, RawMonsterMove, RawMonsterType};
-use crate::world::BiomeDefinition;
 use bevy::reflect::TypeUuid;
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
-#[serde(tag = "type")]
-pub enum DefTypes {
-    Monster(MonsterRawDefinition),
-    Biome(BiomeDefinition),
-    MonsterType(RawMonsterType),
-    MonsterMove(RawMonsterMove),
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A def as written in a def file.
+///
+/// The fields are kept as they are, as a def can leave out fields it inherits from its `parent`.
+/// They are only turned into the raw def of its type once the parents have been merged in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DefEntry {
+    #[serde(rename = "override", skip_serializing_if = "std::ops::Not::not")]
+    pub is_override: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(rename = "abstract", skip_serializing_if = "std::ops::Not::not")]
+    pub is_abstract: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub append: Vec<String>,
+    /// The name of a registered def type, which is checked once all def files are combined.
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub def_name: String,
+    #[serde(flatten)]
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<'de> Deserialize<'de> for DefEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Buffered as a json value, which every supported format can produce.
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let serde_json::Value::Object(mut fields) = value else {
+            return Err(D::Error::custom(format!("invalid def: expected an object, got {}", value)));
+        };
+
+        let is_override = take_field::<bool, D::Error>(&mut fields, "override")?.unwrap_or_default();
+        let parent = take_field::<String, D::Error>(&mut fields, "parent")?;
+        let is_abstract = take_field::<bool, D::Error>(&mut fields, "abstract")?.unwrap_or_default();
+        let append = take_field::<Vec<String>, D::Error>(&mut fields, "append")?.unwrap_or_default();
+        let def_name = take_field::<String, D::Error>(&mut fields, "def_name")?
+            .ok_or_else(|| D::Error::missing_field("def_name"))?;
+        let type_name = take_field::<String, D::Error>(&mut fields, "type")?
+            .ok_or_else(|| D::Error::missing_field("type"))?;
+
+        Ok(DefEntry {
+            is_override,
+            parent,
+            is_abstract,
+            append,
+            type_name,
+            def_name,
+            fields,
+        })
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize, TypeUuid, JsonSchema)]
+/// Removes a field from a def, so only the fields of the def type itself are left.
+fn take_field<T: DeserializeOwned, E: serde::de::Error>(
+    fields: &mut serde_json::Map<String, serde_json::Value>,
+    name: &str,
+) -> Result<Option<T>, E> {
+    fields
+        .remove(name)
+        .map(|value| {
+            serde_json::from_value(value).map_err(|err| E::custom(format!("invalid value for {}: {}", name, err)))
+        })
+        .transpose()
+}
+
+#[derive(Debug, Deserialize, Serialize, TypeUuid)]
 #[uuid = "3423cab8-79ff-4cad-b9ee-0358c005fc1b"]
 pub struct DefsRoot {
-    pub defs: Vec<DefTypes>,
+    pub defs: Vec<DefEntry>,
+    /// Set when the file held a single bare def, instead of a `defs` list.
+    #[serde(skip)]
+    pub is_single_def: bool,
+    /// Set by the loader when the file could not be parsed.
+    #[serde(skip)]
+    pub parse_error: Option<String>,
+}
+
+impl DefsRoot {
+    /// Reads a parsed def file, which is either a `defs` list or a single bare def.
+    ///
+    /// A single def without a `def_name` gets `default_def_name`, which the loader takes from the file name.
+    pub fn from_value(mut value: serde_json::Value, default_def_name: Option<&str>) -> serde_json::Result<Self> {
+        let Some(fields) = value.as_object_mut() else {
+            return DefsRoot::deserialize(value);
+        };
+        if fields.contains_key("defs") {
+            return DefsRoot::deserialize(value);
+        }
+
+        if let Some(def_name) = default_def_name {
+            fields
+                .entry("def_name")
+                .or_insert_with(|| serde_json::Value::String(def_name.to_string()));
+        }
+        let entry = DefEntry::deserialize(value)?;
+
+        Ok(DefsRoot {
+            defs: vec![entry],
+            is_single_def: true,
+            parse_error: None,
+        })
+    }
 }
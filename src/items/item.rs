@@ -0,0 +1,28 @@
+use crate::def_errors::DefFieldError;
+use crate::def_registry::{LinkContext, RawDefinition};
+use crate::prelude::*;
+use bevy::reflect::TypeUuid;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, TypeUuid, JsonSchema, Resource)]
+#[uuid = "a4eb1322-7ff0-461d-9eba-2fec30b333b6"]
+pub struct ItemDefinition {
+    pub def_name: String,
+}
+
+impl RawDefinition for ItemDefinition {
+    type Definition = ItemDefinition;
+
+    fn link(&self, _context: &LinkContext) -> Result<ItemDefinition, DefFieldError> {
+        Ok(self.clone())
+    }
+}
+
+impl Definition for ItemDefinition {
+    const TYPE_NAME: &'static str = "Item";
+
+    fn get_def_name(&self) -> &str {
+        &self.def_name
+    }
+}
@@ -0,0 +1,14 @@
+mod item;
+
+pub use item::*;
+
+use crate::def_registry::RegisterDefType;
+use crate::prelude::*;
+
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_def_type::<ItemDefinition>();
+    }
+}
@@ -1,16 +1,90 @@
-use crate::def_database::DefDatabase;
-use crate::def_types::{DefTypes, DefsRoot};
-use crate::monsters::{MonsterDefinition, MonsterMove, MonsterType};
+use crate::def_cache::load_def_cache;
+use crate::def_errors::{DefLoadError, DefLoadReport, DefLocation};
+use crate::def_packs::{
+    collect_packs, DefSource, PackManifest, PackManifestLoader, PACKS_FOLDER, PACK_MANIFEST_FILE,
+};
+use crate::def_registry::{AssetReference, DefDatabases, DefTypeRegistry, SourcedDef};
+use crate::def_types::{DefEntry, DefsRoot};
+use crate::localization::{Locale, LocaleTable, LocaleTableLoader, LocalizedStrings, TranslationReport};
 use crate::prelude::*;
-use crate::world::BiomeDefinition;
-use bevy::asset::Error;
+use bevy::asset::{Error, FileAssetIo, LoadState};
+use bevy::ecs::event::ManualEventReader;
 use bevy::{
-    asset::{AssetLoader, LoadContext, LoadedAsset},
+    asset::{AssetLoader, HandleId, LoadContext, LoadedAsset},
     utils::BoxedFuture,
 };
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 struct DefsLoader;
 
+/// The file formats def files can be written in. All of them deserialize into the same [`DefsRoot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DefFormat {
+    Json,
+    Ron,
+    Toml,
+    Yaml,
+}
+
+impl DefFormat {
+    /// The extensions handled by the loader. The other formats use a `defs.` prefix,
+    /// so they do not claim files meant for other loaders, like `scn.ron` scenes.
+    const EXTENSIONS: &'static [&'static str] = &["json", "defs.ron", "defs.toml", "defs.yaml"];
+
+    fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        if file_name.ends_with(".defs.ron") {
+            Some(DefFormat::Ron)
+        } else if file_name.ends_with(".defs.toml") {
+            Some(DefFormat::Toml)
+        } else if file_name.ends_with(".defs.yaml") {
+            Some(DefFormat::Yaml)
+        } else if file_name.ends_with(".json") {
+            Some(DefFormat::Json)
+        } else {
+            None
+        }
+    }
+
+    /// Parses a def file. `default_def_name` is used for a single bare def that does not set its own name.
+    fn parse(self, bytes: &[u8], default_def_name: Option<&str>) -> Result<DefsRoot, String> {
+        // Everything goes through a json value first, so a bare def can be told apart from a `defs` list.
+        let value: serde_json::Value = match self {
+            DefFormat::Json => serde_json::from_slice(bytes).map_err(|err| err.to_string())?,
+            DefFormat::Ron => ron::de::from_bytes(bytes).map_err(|err| err.to_string())?,
+            DefFormat::Toml => std::str::from_utf8(bytes)
+                .map_err(|err| err.to_string())
+                .and_then(|text| toml::from_str(text).map_err(|err| err.to_string()))?,
+            DefFormat::Yaml => serde_yaml::from_slice(bytes).map_err(|err| err.to_string())?,
+        };
+        DefsRoot::from_value(value, default_def_name).map_err(|err| err.to_string())
+    }
+}
+
+/// The file name without its def extension, like `emberpup` for `monsters/fire/emberpup.json`.
+fn def_name_from_path(path: &Path) -> Option<&str> {
+    let file_name = path.file_name()?.to_str()?;
+    DefFormat::EXTENSIONS
+        .iter()
+        .find_map(|extension| file_name.strip_suffix(extension)?.strip_suffix('.'))
+}
+
+/// Parses a def file. A broken file still produces a root, so the error ends up in the load report
+/// instead of stalling the loading state.
+fn parse_defs_root(bytes: &[u8], path: &Path) -> DefsRoot {
+    // The loader is only picked for the registered extensions, which all map to a format.
+    let format = DefFormat::from_path(path).unwrap_or(DefFormat::Json);
+    format
+        .parse(bytes, def_name_from_path(path))
+        .unwrap_or_else(|message| DefsRoot {
+            defs: Vec::new(),
+            is_single_def: false,
+            parse_error: Some(message),
+        })
+}
+
 impl AssetLoader for DefsLoader {
     fn load<'a>(
         &'a self,
@@ -18,167 +92,1482 @@ impl AssetLoader for DefsLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), Error>> {
         Box::pin(async move {
-            let raw_definition: DefsRoot = serde_json::from_slice(bytes)?;
+            let raw_definition = parse_defs_root(bytes, load_context.path());
             load_context.set_default_asset(LoadedAsset::new(raw_definition));
             Ok(())
         })
     }
 
     fn extensions(&self) -> &[&str] {
-        &["json"]
+        DefFormat::EXTENSIONS
+    }
+}
+
+/// What to do when the defs fail to load.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DefLoadStrictness {
+    /// Log the errors, and continue with the defs that did load.
+    #[default]
+    Lenient,
+    /// Stop in [`GameState::DefLoadFailed`], where the report is shown.
+    Strict,
+    /// Exit the process with a non-zero status, for running in content CI.
+    FailFast,
+}
+
+impl DefLoadStrictness {
+    /// Picks the strictness from the `--strict-defs` and `--fail-fast-defs` command line flags.
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        if args.iter().any(|arg| arg == "--fail-fast-defs") {
+            DefLoadStrictness::FailFast
+        } else if args.iter().any(|arg| arg == "--strict-defs") {
+            DefLoadStrictness::Strict
+        } else {
+            DefLoadStrictness::Lenient
+        }
     }
 }
 
-pub struct DefPlugin;
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub struct DefLoadSettings {
+    pub strictness: DefLoadStrictness,
+}
+
+#[derive(Default)]
+pub struct DefPlugin {
+    pub strictness: DefLoadStrictness,
+}
 
 impl Plugin for DefPlugin {
     fn build(&self, app: &mut App) {
         app.add_asset::<DefsRoot>()
+            .add_asset::<PackManifest>()
+            .add_asset::<LocaleTable>()
+            .insert_resource(DefLoadSettings {
+                strictness: self.strictness,
+            })
             .add_asset_loader(DefsLoader)
-            .add_collection_to_loading_state::<_, DefAssets>(GameState::LoadingFromDisk)
+            .add_asset_loader(PackManifestLoader)
+            .add_asset_loader(LocaleTableLoader)
+            .add_collection_to_loading_state::<_, DefAssets>(GameState::LoadingDefs)
+            .add_event::<DefsReloaded>()
+            .init_resource::<DefTypeRegistry>()
+            .init_resource::<DefLoadReport>()
+            .init_resource::<Locale>()
+            .init_resource::<LocalizedStrings>()
+            .init_resource::<TranslationReport>()
+            .add_system(load_def_cache.in_schedule(OnEnter(GameState::CheckingDefCache)))
             .add_system(
                 add_defs_to_database.in_schedule(OnEnter(GameState::AddingToDatabase)),
-            );
+            )
+            .add_system(validate_asset_references.run_if(in_state(GameState::ValidatingAssets)))
+            .add_system(show_def_load_report.in_schedule(OnEnter(GameState::DefLoadFailed)))
+            .add_system(reload_changed_defs.run_if(in_state(GameState::Playing)))
+            .add_system(apply_pending_reload.after(reload_changed_defs).run_if(in_state(GameState::Playing)));
     }
 }
 
+/// The folder in the assets that def files are loaded from, including any nested folders.
+pub const DEFS_FOLDER: &str = "defs";
+
+/// The paths have to be literals, so they repeat [`DEFS_FOLDER`], [`PACKS_FOLDER`] and [`LOCALE_FOLDER`], which
+/// a test keeps in sync.
+///
+/// [`LOCALE_FOLDER`]: crate::localization::LOCALE_FOLDER
 #[derive(AssetCollection, Resource)]
 pub struct DefAssets {
+    /// Folders are loaded recursively, so defs can be split up like `defs/monsters/fire/emberpup.json`.
     #[asset(path = "defs", collection(typed))]
     pub defs: Vec<Handle<DefsRoot>>,
+    /// The content packs, each in its own folder with a [`PackManifest`] next to its def files.
+    #[asset(path = "packs", collection)]
+    pub packs: Vec<HandleUntyped>,
+    /// A string table per locale, with the player facing names and descriptions of the defs.
+    #[asset(path = "locale", collection(typed))]
+    pub locale_tables: Vec<Handle<LocaleTable>>,
 }
 
-macro_rules! try_unpack {
-    ($variant:path, $value:expr) => {
-        if let $variant(x) = $value {
-            Some(x)
-        } else {
-            None
+/// Sent when changed def files have been applied to the databases while playing.
+pub struct DefsReloaded {
+    pub changed: Vec<ReloadedDef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadedDef {
+    pub type_name: &'static str,
+    pub def_name: String,
+}
+
+fn asset_path(asset_server: &AssetServer, handle: impl Into<HandleId>) -> String {
+    asset_server
+        .get_handle_path(handle)
+        .map(|p| p.path().to_string_lossy().replace('\\', "/"))
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+/// The assets folder on disk, or `None` when the assets are not loaded from files.
+pub fn asset_folder(asset_server: &AssetServer) -> Option<PathBuf> {
+    asset_server
+        .asset_io()
+        .downcast_ref::<FileAssetIo>()
+        .map(|asset_io| asset_io.root_path().clone())
+}
+
+fn collect_sources<'a>(
+    asset_server: &AssetServer,
+    def_assets: &DefAssets,
+    raw_definitions: &'a Assets<DefsRoot>,
+    pack_manifests: &Assets<PackManifest>,
+    errors: &mut Vec<DefLoadError>,
+) -> Vec<DefSource<'a>> {
+    let base_files = def_assets
+        .defs
+        .iter()
+        .filter_map(|handle| Some((asset_path(asset_server, handle), raw_definitions.get(handle)?)))
+        .collect();
+
+    // The pack folders hold both manifests and def files, so the handles are sorted out by asset type.
+    let mut manifests = Vec::new();
+    let mut pack_files = Vec::new();
+    for handle in def_assets.packs.iter() {
+        let path = asset_path(asset_server, handle);
+        if let Some(manifest) = pack_manifests.get(&handle.clone_weak().typed::<PackManifest>()) {
+            manifests.push((path, manifest.clone()));
+        } else if let Some(root) = raw_definitions.get(&handle.clone_weak().typed::<DefsRoot>()) {
+            pack_files.push((path, root));
+        }
+    }
+
+    collect_packs(base_files, manifests, pack_files, errors)
+}
+
+fn build_databases(
+    registry: &DefTypeRegistry,
+    asset_server: &AssetServer,
+    sources: &[DefSource],
+    errors: &mut Vec<DefLoadError>,
+) -> DefDatabases {
+    let all_definitions = resolve_defs(registry, sources, errors);
+    registry.build_databases(&all_definitions, asset_server, errors)
+}
+
+/// Applies the overrides of the packs and merges every def with its parents.
+fn resolve_defs(registry: &DefTypeRegistry, sources: &[DefSource], errors: &mut Vec<DefLoadError>) -> Vec<SourcedDef> {
+    let all_entries = resolve_overrides(registry, sources, errors);
+    resolve_inheritance(&all_entries, errors)
+}
+
+/// Loads the def files straight from disk, through the same pipeline as the game, but without an [`App`] running.
+///
+/// `asset_folder` is the root of the assets, the defs are read from its `defs` folder and the packs in its
+/// `packs` folder.
+pub fn load_defs_from_disk(
+    registry: &DefTypeRegistry,
+    asset_server: &AssetServer,
+    asset_folder: &Path,
+) -> (DefDatabases, DefLoadReport) {
+    let (_, databases, report) = load_resolved_defs_from_disk(registry, asset_server, asset_folder);
+    (databases, report)
+}
+
+/// Like [`load_defs_from_disk`], but also returns the fields of every def as resolved from the def files,
+/// before they are linked.
+pub fn load_resolved_defs_from_disk(
+    registry: &DefTypeRegistry,
+    asset_server: &AssetServer,
+    asset_folder: &Path,
+) -> (Vec<SourcedDef>, DefDatabases, DefLoadReport) {
+    let mut errors = Vec::new();
+
+    let base_roots = read_def_files(asset_folder, DEFS_FOLDER, &mut errors);
+
+    let mut manifests = Vec::new();
+    let mut pack_roots = Vec::new();
+    // Unlike the defs folder, the packs folder is optional.
+    if asset_folder.join(PACKS_FOLDER).is_dir() {
+        pack_roots = read_def_files(asset_folder, PACKS_FOLDER, &mut errors);
+        manifests = read_pack_manifests(asset_folder, &mut errors);
+    }
+
+    let sources = collect_packs(borrow_roots(&base_roots), manifests, borrow_roots(&pack_roots), &mut errors);
+    let resolved = resolve_defs(registry, &sources, &mut errors);
+    let databases = registry.build_databases(&resolved, asset_server, &mut errors);
+
+    (resolved, databases, DefLoadReport { errors })
+}
+
+fn borrow_roots(roots: &[(String, DefsRoot)]) -> Vec<(String, &DefsRoot)> {
+    roots.iter().map(|(path, root)| (path.clone(), root)).collect()
+}
+
+/// Use the asset path, so errors read the same as when running the game.
+fn to_asset_path(asset_folder: &Path, file: &Path) -> String {
+    file.strip_prefix(asset_folder)
+        .unwrap_or(file)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Reads and parses all def files below `folder`, as `(asset path, root)` pairs.
+fn read_def_files(asset_folder: &Path, folder: &str, errors: &mut Vec<DefLoadError>) -> Vec<(String, DefsRoot)> {
+    let mut files = Vec::new();
+    if let Err(err) = find_def_files(&asset_folder.join(folder), &mut files) {
+        errors.push(DefLoadError::Parse {
+            source_path: folder.to_string(),
+            message: err.to_string(),
+        });
+    }
+
+    files
+        .iter()
+        .map(|file| {
+            let root = match std::fs::read(file) {
+                Ok(bytes) => parse_defs_root(&bytes, file),
+                Err(err) => DefsRoot {
+                    defs: Vec::new(),
+                    is_single_def: false,
+                    parse_error: Some(err.to_string()),
+                },
+            };
+            (to_asset_path(asset_folder, file), root)
+        })
+        .collect()
+}
+
+/// Reads the manifest in each folder of the packs folder, as `(asset path, manifest)` pairs.
+fn read_pack_manifests(asset_folder: &Path, errors: &mut Vec<DefLoadError>) -> Vec<(String, PackManifest)> {
+    let entries = match std::fs::read_dir(asset_folder.join(PACKS_FOLDER)) {
+        Ok(entries) => entries,
+        Err(err) => {
+            errors.push(DefLoadError::Parse {
+                source_path: PACKS_FOLDER.to_string(),
+                message: err.to_string(),
+            });
+            return Vec::new();
         }
     };
+
+    let mut manifests = Vec::new();
+    for entry in entries.flatten() {
+        let file = entry.path().join(PACK_MANIFEST_FILE);
+        // Folders without a manifest are reported when their def files are grouped into packs.
+        let Ok(bytes) = std::fs::read(&file) else {
+            continue;
+        };
+        let path = to_asset_path(asset_folder, &file);
+        let manifest = PackManifest::parse(&bytes, &path);
+        manifests.push((path, manifest));
+    }
+    manifests
+}
+
+fn find_def_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_def_files(&path, files)?;
+        } else if DefFormat::from_path(&path).is_some()
+            && path.file_name().and_then(|name| name.to_str()) != Some(PACK_MANIFEST_FILE)
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn add_defs_to_database(
     asset_server: Res<AssetServer>,
+    def_assets: Res<DefAssets>,
     raw_definitions: Res<Assets<DefsRoot>>,
+    pack_manifests: Res<Assets<PackManifest>>,
+    locale_tables: Res<Assets<LocaleTable>>,
+    registry: Res<DefTypeRegistry>,
     mut commands: Commands,
+    mut report: ResMut<DefLoadReport>,
     mut state: ResMut<NextState<GameState>>,
 ) {
     let mut errors = Vec::new();
 
-    let all_definitions: Vec<&DefTypes> = raw_definitions.iter().flat_map(|d| &d.1.defs).collect();
+    let sources = collect_sources(&asset_server, &def_assets, &raw_definitions, &pack_manifests, &mut errors);
+    let mut databases = build_databases(&registry, &asset_server, &sources, &mut errors);
 
-    let biomes = create_biome_defs(&all_definitions);
+    let strings = LocalizedStrings::from_tables(def_assets.locale_tables.iter().filter_map(|handle| {
+        Some((asset_path(&asset_server, handle), locale_tables.get(handle)?))
+    }));
+    let translations = strings.find_missing_translations(&registry.def_names(&databases));
+    if !translations.is_complete() {
+        warn!("{}", translations);
+    }
+    commands.insert_resource(strings);
+    commands.insert_resource(translations);
 
-    info!("Loaded {} biomes", biomes.len());
+    commands.insert_resource(PendingAssetReferences::new(std::mem::take(&mut databases.asset_references)));
+    registry.insert_resources(&mut commands, databases);
 
-    let monster_types = create_monster_type_defs(&all_definitions, &mut errors);
+    *report = DefLoadReport { errors };
+    state.set(GameState::ValidatingAssets);
+}
 
-    info!("Loaded {} monster types", monster_types.len());
+/// The assets referenced by defs that have not finished loading yet, along with whether their file has loaded.
+#[derive(Resource, Default)]
+pub(crate) struct PendingAssetReferences {
+    references: Vec<(AssetReference, bool)>,
+}
 
-    let monster_moves = create_monster_move_defs(&all_definitions, &monster_types, &mut errors);
+impl PendingAssetReferences {
+    pub(crate) fn new(references: Vec<AssetReference>) -> Self {
+        PendingAssetReferences {
+            references: references.into_iter().map(|reference| (reference, false)).collect(),
+        }
+    }
 
-    info!("Loaded {} monster moves", monster_moves.len());
+    /// Drops the references that finished loading, returning errors for the ones that failed to load or do not
+    /// have the sub-asset named by their label.
+    fn poll(&mut self, world: &World) -> Vec<DefLoadError> {
+        let asset_server = world.resource::<AssetServer>();
+        let mut errors = Vec::new();
+        self.references.retain_mut(|(reference, file_loaded)| {
+            match asset_server.get_load_state(&reference.handle) {
+                LoadState::Failed => {
+                    errors.push(asset_error(reference, "failed to load"));
+                    false
+                }
+                LoadState::Loaded if reference.is_loaded(world) => false,
+                // The sub-assets of a file are stored the frame after it has loaded, so they get one more frame.
+                LoadState::Loaded if *file_loaded => {
+                    let message = match reference.path.split_once('#') {
+                        Some((_, label)) => format!("has no sub-asset '{}'", label),
+                        None => "is not of the expected type".to_string(),
+                    };
+                    errors.push(asset_error(reference, &message));
+                    false
+                }
+                LoadState::Loaded => {
+                    *file_loaded = true;
+                    true
+                }
+                _ => true,
+            }
+        });
+        errors
+    }
 
-    let monsters = create_monster_defs(&asset_server, &all_definitions, &biomes, &monster_moves, &monster_types, &mut errors);
+    fn is_empty(&self) -> bool {
+        self.references.is_empty()
+    }
+}
 
-    info!("Loaded {} monsters", monsters.len());
+/// Waits for the assets the defs refer to, reporting the ones that fail to load or do not have the sub-asset
+/// named by their label. Then decides whether the game can start, based on all errors found while loading.
+fn validate_asset_references(world: &mut World) {
+    world.resource_scope(|world, mut pending: Mut<PendingAssetReferences>| {
+        let errors = pending.poll(world);
+        world.resource_mut::<DefLoadReport>().errors.extend(errors);
+    });
 
-    commands.insert_resource(biomes);
-    commands.insert_resource(monsters);
-    commands.insert_resource(monster_moves);
-    commands.insert_resource(monster_types);
+    if !world.resource::<PendingAssetReferences>().is_empty() {
+        return;
+    }
+    world.remove_resource::<PendingAssetReferences>();
 
-    if !errors.is_empty() {
-        error!("Failed to load some definitions: {:?}", errors);
+    let report = world.resource::<DefLoadReport>();
+    let next_state = if report.is_ok() {
+        info!("{}", *report);
+        GameState::Playing
     } else {
-        info!("All definitions loaded without errors");
+        error!("{}", *report);
+        match world.resource::<DefLoadSettings>().strictness {
+            DefLoadStrictness::Lenient => GameState::Playing,
+            DefLoadStrictness::Strict => GameState::DefLoadFailed,
+            DefLoadStrictness::FailFast => std::process::exit(1),
+        }
+    };
+    world.resource_mut::<NextState<GameState>>().set(next_state);
+}
+
+/// The font of the screen shown in [`GameState::DefLoadFailed`].
+const REPORT_FONT: &str = "fonts/DejaVuSansMono.ttf";
+
+/// Shows the report on screen, as the game stops here rather than starting with broken defs.
+fn show_def_load_report(mut commands: Commands, asset_server: Res<AssetServer>, report: Res<DefLoadReport>) {
+    commands.spawn(Camera2dBundle::default());
+    let style = TextStyle {
+        font: asset_server.load(REPORT_FONT),
+        font_size: 16.0,
+        color: Color::WHITE,
+    };
+    commands.spawn(
+        TextBundle::from_sections([
+            TextSection::new("The definitions failed to load, so the game cannot start.\n\n", style.clone()),
+            TextSection::new(report.to_string(), style),
+        ])
+        .with_style(Style {
+            margin: UiRect::all(Val::Px(16.0)),
+            max_size: Size::width(Val::Percent(100.0)),
+            ..default()
+        }),
+    );
+}
+
+fn asset_error(reference: &AssetReference, message: &str) -> DefLoadError {
+    DefLoadError::InvalidAsset {
+        location: reference.location.clone(),
+        pointer: reference.pointer.clone(),
+        path: reference.path.clone(),
+        message: message.to_string(),
     }
+}
+
+/// Rebuilds all databases when a def file changes on disk, leaving them to [`apply_pending_reload`] once the
+/// assets they refer to have loaded.
+///
+/// The reload is all or nothing: if the changed files do not load cleanly, or remove defs that might
+/// still be referenced, the current databases are left untouched.
+///
+/// Runs as an exclusive system, as the databases of the registered def types are not known up front.
+///
+/// Defs loaded from the def cache are not reloaded, as the def files were never loaded.
+fn reload_changed_defs(world: &mut World, mut asset_events: Local<ManualEventReader<AssetEvent<DefsRoot>>>) {
+    if !world.contains_resource::<DefAssets>() {
+        return;
+    }
+
+    let modified = asset_events
+        .iter(world.resource::<Events<AssetEvent<DefsRoot>>>())
+        .any(|event| matches!(event, AssetEvent::Modified { .. }));
+    if !modified {
+        return;
+    }
+
+    info!("Def files changed, reloading definitions");
+
+    world.resource_scope(|world, registry: Mut<DefTypeRegistry>| {
+        let mut errors = Vec::new();
+        let mut reloaded = {
+            let sources = collect_sources(
+                world.resource::<AssetServer>(),
+                world.resource::<DefAssets>(),
+                world.resource::<Assets<DefsRoot>>(),
+                world.resource::<Assets<PackManifest>>(),
+                &mut errors,
+            );
+            build_databases(&registry, world.resource::<AssetServer>(), &sources, &mut errors)
+        };
+
+        registry.find_removed_defs(world, &reloaded, &mut errors);
+
+        let mut report = world.resource_mut::<DefLoadReport>();
+        *report = DefLoadReport { errors };
+        if !report.is_ok() {
+            error!("Refusing to reload definitions. {}", *report);
+            return;
+        }
 
-    state.set(GameState::Playing);
+        // Replaces a reload still waiting for its assets, as these defs are newer.
+        let assets = PendingAssetReferences::new(std::mem::take(&mut reloaded.asset_references));
+        world.insert_resource(PendingReload {
+            databases: reloaded,
+            assets,
+            errors: Vec::new(),
+        });
+    });
 }
 
-fn create_biome_defs(all_definitions: &[&DefTypes]) -> DefDatabase<BiomeDefinition> {
-    all_definitions
-        .iter()
-        .filter_map(|d| try_unpack!(DefTypes::Biome, d))
-        .cloned()
-        .collect()
+/// Reloaded defs waiting for the assets they refer to, so a typo in a model path is caught like on startup.
+#[derive(Resource)]
+struct PendingReload {
+    databases: DefDatabases,
+    assets: PendingAssetReferences,
+    errors: Vec<DefLoadError>,
 }
 
-fn create_monster_type_defs(
-    all_definitions: &[&DefTypes],
-    errors: &mut Vec<Error>,
-) -> DefDatabase<MonsterType> {
-    let mut monster_types = all_definitions
-        .iter()
-        .filter_map(|d| try_unpack!(DefTypes::MonsterType, d).and_then(|t| Some(t.to_definition())))
-        .collect();
+/// Applies the reloaded defs in place once their assets have loaded, unless one of them failed to.
+fn apply_pending_reload(world: &mut World) {
+    let Some(mut pending) = world.remove_resource::<PendingReload>() else {
+        return;
+    };
+    let errors = pending.assets.poll(world);
+    pending.errors.extend(errors);
+    if !pending.assets.is_empty() {
+        world.insert_resource(pending);
+        return;
+    }
+
+    let report = DefLoadReport { errors: pending.errors };
+    if !report.is_ok() {
+        error!("Refusing to reload definitions. {}", report);
+        *world.resource_mut::<DefLoadReport>() = report;
+        return;
+    }
+
+    world.resource_scope(|world, registry: Mut<DefTypeRegistry>| {
+        let changed = registry.apply_reloaded_defs(world, &pending.databases);
+
+        info!("Reloaded {} changed definitions", changed.len());
+
+        if !changed.is_empty() {
+            world.send_event(DefsReloaded { changed });
+        }
+    });
+}
+
+/// A def entry along with where it was loaded from.
+struct SourcedEntry<'a> {
+    location: DefLocation,
+    /// Owned when an override has been merged into it.
+    entry: Cow<'a, DefEntry>,
+}
+
+/// Flattens the defs of all files into one list, where each def type and name pair occurs only once.
+///
+/// A def with the same type and name as an earlier one is an error, unless it is marked as `override`,
+/// in which case it is merged into the earlier def, see [`merge_override`].
+fn resolve_overrides<'a>(
+    registry: &DefTypeRegistry,
+    sources: &[DefSource<'a>],
+    errors: &mut Vec<DefLoadError>,
+) -> Vec<SourcedEntry<'a>> {
+    let mut resolved: Vec<SourcedEntry<'a>> = Vec::new();
+    let mut index_by_key: HashMap<(&'static str, &'a str), usize> = HashMap::new();
+
+    for source in sources.iter() {
+        let root = source.root;
+        if let Some(message) = &root.parse_error {
+            errors.push(DefLoadError::Parse {
+                source_path: source.path.clone(),
+                message: message.clone(),
+            });
+        }
+
+        for (index, entry) in root.defs.iter().enumerate() {
+            let index = (!root.is_single_def).then_some(index);
+            let Some(type_name) = registry.find_type_name(&entry.type_name) else {
+                let pointer = match index {
+                    Some(index) => format!("/defs/{}/type", index),
+                    None => "/type".to_string(),
+                };
+                errors.push(DefLoadError::UnknownType {
+                    source_path: source.path.clone(),
+                    pointer,
+                    type_name: entry.type_name.clone(),
+                });
+                continue;
+            };
 
-    for d in all_definitions.iter() {
-        if let DefTypes::MonsterType(t) = d {
-            match t.link_definitions(&monster_types) {
-                Ok(def) => {
-                    monster_types.replace(def);
+            let key = (type_name, entry.def_name.as_str());
+            let sourced = SourcedEntry {
+                location: DefLocation {
+                    pack_id: source.pack_id.clone(),
+                    source_path: source.path.clone(),
+                    index,
+                    type_name: key.0,
+                    def_name: key.1.to_string(),
                 },
-                Err(err) => {
-                    errors.push(err);
+                entry: Cow::Borrowed(entry),
+            };
+            match index_by_key.get(&key) {
+                Some(&existing) if entry.is_override => {
+                    let merged = merge_override(&resolved[existing].entry, entry);
+                    resolved[existing] = SourcedEntry {
+                        location: sourced.location,
+                        entry: Cow::Owned(merged),
+                    };
+                }
+                Some(&existing) => {
+                    let previous = &resolved[existing].location;
+                    errors.push(DefLoadError::DuplicateName {
+                        location: sourced.location,
+                        previous_pack_id: previous.pack_id.clone(),
+                        previous_source_path: previous.source_path.clone(),
+                    });
+                }
+                None => {
+                    if entry.is_override {
+                        warn!(
+                            "{} is marked as override, but there is nothing to override",
+                            sourced.location
+                        );
+                    }
+                    index_by_key.insert(key, resolved.len());
+                    resolved.push(sourced);
                 }
             }
         }
     }
 
-    monster_types
+    resolved
+}
+
+/// Patches a def with an override, so a pack only has to set the fields it changes.
+///
+/// Fields set on the override replace the fields of the def, except for the lists named in its `append`,
+/// which are added to the end of the list of the def, like for a parent.
+fn merge_override(earlier: &DefEntry, patch: &DefEntry) -> DefEntry {
+    let mut merged = earlier.clone();
+    for (name, value) in patch.fields.iter() {
+        if patch.append.contains(name) {
+            if let (Some(serde_json::Value::Array(list)), serde_json::Value::Array(added)) =
+                (merged.fields.get_mut(name), value)
+            {
+                list.extend(added.iter().cloned());
+                continue;
+            }
+        }
+        merged.fields.insert(name.clone(), value.clone());
+    }
+    if patch.parent.is_some() {
+        merged.parent = patch.parent.clone();
+    }
+    merged.is_abstract |= patch.is_abstract;
+    merged
 }
 
-fn create_monster_move_defs(
-    all_definitions: &[&DefTypes],
-    move_types: &DefDatabase<MonsterType>,
-    errors: &mut Vec<Error>,
-) -> DefDatabase<MonsterMove> {
-    all_definitions
+/// Merges every def with its parents, leaving out the abstract defs.
+///
+/// Fields set on a def replace the fields of its parent, except for the lists named in `append`,
+/// which are added to the end of the list of the parent.
+fn resolve_inheritance(entries: &[SourcedEntry], errors: &mut Vec<DefLoadError>) -> Vec<SourcedDef> {
+    let index_by_key: HashMap<(&str, &str), usize> = entries
         .iter()
-        .filter_map(|d| {
-            try_unpack!(DefTypes::MonsterMove, d).and_then(|t| {
-                match t.to_definition(move_types) {
-                    Ok(def) => Some(def),
-                    Err(err) => {
-                        errors.push(err);
-                        None
-                    }
+        .enumerate()
+        .map(|(i, sourced)| ((sourced.location.type_name, sourced.location.def_name.as_str()), i))
+        .collect();
+
+    let mut resolved = Vec::new();
+    for sourced in entries.iter().filter(|sourced| !sourced.entry.is_abstract) {
+        let fields = match inherited_fields(sourced, entries, &index_by_key) {
+            Ok(fields) => fields,
+            Err(err) => {
+                // Every def below a broken parent runs into the same error, so only report it once.
+                if !errors.contains(&err) {
+                    errors.push(err);
                 }
-            })
-        })
-        .collect()
+                continue;
+            }
+        };
+
+        resolved.push(SourcedDef {
+            location: sourced.location.clone(),
+            fields,
+        });
+    }
+
+    resolved
 }
 
-fn create_monster_defs(
-    asset_server: &Res<AssetServer>,
-    all_definitions: &[&DefTypes],
-    biomes: &DefDatabase<BiomeDefinition>,
-    moves: &DefDatabase<MonsterMove>,
-    types: &DefDatabase<MonsterType>,
-    errors: &mut Vec<Error>,
-) -> DefDatabase<MonsterDefinition> {
-    all_definitions
-        .iter()
-        .filter_map(|d| {
-            try_unpack!(DefTypes::Monster, d).and_then(|m| {
-                match m.to_definition(asset_server, biomes, moves, types) {
-                    Ok(def) => Some(def),
-                    Err(err) => {
-                        errors.push(err);
-                        None
-                    }
+/// Collects the fields of a def and all of its parents, where the fields closest to the def win.
+fn inherited_fields(
+    sourced: &SourcedEntry,
+    entries: &[SourcedEntry],
+    index_by_key: &HashMap<(&str, &str), usize>,
+) -> Result<serde_json::Map<String, serde_json::Value>, DefLoadError> {
+    let mut chain = vec![sourced];
+    let mut current = sourced;
+    while let Some(parent_name) = &current.entry.parent {
+        let location = &current.location;
+        let Some(&parent_index) = index_by_key.get(&(location.type_name, parent_name.as_str())) else {
+            return Err(DefLoadError::UnknownReference {
+                location: location.clone(),
+                pointer: location.pointer("/parent"),
+                target_type: location.type_name,
+                target_name: parent_name.clone(),
+            });
+        };
+
+        let parent = &entries[parent_index];
+        if let Some(start) = chain.iter().position(|visited| std::ptr::eq(*visited, parent)) {
+            let mut cycle: Vec<String> = chain[start..]
+                .iter()
+                .map(|visited| visited.location.def_name.clone())
+                .collect();
+            cycle.push(parent.location.def_name.clone());
+            return Err(DefLoadError::InheritanceCycle {
+                location: location.clone(),
+                cycle,
+            });
+        }
+
+        chain.push(parent);
+        current = parent;
+    }
+
+    let mut fields = serde_json::Map::new();
+    for sourced in chain.iter().rev() {
+        for (name, value) in sourced.entry.fields.iter() {
+            if sourced.entry.append.contains(name) {
+                if let (Some(serde_json::Value::Array(inherited)), serde_json::Value::Array(added)) =
+                    (fields.get_mut(name), value)
+                {
+                    inherited.extend(added.iter().cloned());
+                    continue;
                 }
-            })
-        })
-        .collect()
+            }
+            fields.insert(name.clone(), value.clone());
+        }
+    }
+
+    Ok(fields)
 }
 
-pub fn output_json_schema() {
-    let schema = schemars::schema_for!(DefsRoot);
-    let schema_file = std::fs::File::create("assets/def_schema.json")
-        .expect("Failed to create schema json file on disk");
-    serde_json::to_writer_pretty(schema_file, &schema).expect("Failed to schema to json file");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::headless_app;
+    use crate::def_packs::BASE_PACK_ID;
+    use crate::localization::LOCALE_FOLDER;
+    use crate::monsters::{MonsterDefinition, MonsterMove, MonsterType, MoveCategory};
+    use crate::world::BiomeDefinition;
+    use serde_json::{json, Value};
+
+    /// An app with all def types of the game registered, to load test defs with.
+    fn test_app() -> App {
+        headless_app("assets")
+    }
+
+    fn parse(json: &str) -> DefsRoot {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn source<'a>(path: &str, root: &'a DefsRoot) -> DefSource<'a> {
+        DefSource {
+            pack_id: BASE_PACK_ID.to_string(),
+            path: path.to_string(),
+            root,
+        }
+    }
+
+    fn build_test_databases(app: &App, resolved: &[SourcedDef], errors: &mut Vec<DefLoadError>) -> DefDatabases {
+        let registry = app.world.resource::<DefTypeRegistry>();
+        registry.build_databases(resolved, app.world.resource::<AssetServer>(), errors)
+    }
+
+    /// A Monster def of type `normal` with the given fields, filling in the fields a test is not about.
+    fn monster_def(fields: Value) -> String {
+        let mut def = json!({
+            "type": "Monster",
+            "model_path": "monsters/001/model.glb#Scene0",
+            "types": ["normal"],
+            "spawn_locations": [],
+            "move_learn_set": [],
+            "base_stats": {
+                "hp": 40,
+                "mp": 20,
+                "physical_attack": 12,
+                "physical_defense": 10,
+                "magical_attack": 8,
+                "magical_defense": 9,
+                "speed": 10
+            },
+            "stat_growth": {
+                "hp": "Linear",
+                "mp": "Linear",
+                "physical_attack": "Linear",
+                "physical_defense": "Linear",
+                "magical_attack": "Linear",
+                "magical_defense": "Linear",
+                "speed": "Linear"
+            }
+        });
+        for (name, value) in fields.as_object().unwrap() {
+            def[name] = value.clone();
+        }
+        def.to_string()
+    }
+
+    /// A MonsterMove def of type `normal` with the given fields, filling in the fields a test is not about.
+    fn move_def(fields: Value) -> String {
+        let mut def = json!({
+            "type": "MonsterMove",
+            "move_type_def_name": "normal",
+            "base_mp_usage": 0,
+            "base_damage": 40,
+            "base_accuracy": 1,
+            "base_crit_chance": 0.05,
+            "base_crit_multiplier": 2,
+            "post_move_speed": 1,
+            "max_uses": 20,
+            "category": "Physical"
+        });
+        for (name, value) in fields.as_object().unwrap() {
+            def[name] = value.clone();
+        }
+        def.to_string()
+    }
+
+    /// Loads the defs of `json` as the file `defs/a.json`, returning the databases and the errors found on the way.
+    fn load_test_defs(json: &str) -> (DefDatabases, Vec<DefLoadError>) {
+        let root = parse(json);
+        let sources = vec![source("defs/a.json", &root)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let entries = resolve_overrides(app.world.resource(), &sources, &mut errors);
+        let resolved = resolve_inheritance(&entries, &mut errors);
+        let databases = build_test_databases(&app, &resolved, &mut errors);
+        (databases, errors)
+    }
+
+    #[test]
+    fn def_assets_load_the_content_folders() {
+        let source = include_str!("json_asset_definition.rs");
+        for folder in [DEFS_FOLDER, PACKS_FOLDER, LOCALE_FOLDER] {
+            let attribute = format!("#[asset(path = \"{}\"", folder);
+            assert!(source.contains(&attribute), "DefAssets does not load the '{}' folder", folder);
+        }
+    }
+
+    #[test]
+    fn duplicate_defs_are_reported_with_both_files() {
+        let first = parse(r#"{"defs": [{"type": "Biome", "def_name": "plains"}]}"#);
+        let second = parse(r#"{"defs": [{"type": "Biome", "def_name": "plains"}]}"#);
+        let sources = vec![source("defs/a.json", &first), source("defs/b.json", &second)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let resolved = resolve_overrides(app.world.resource(), &sources, &mut errors);
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(
+            errors,
+            vec![DefLoadError::DuplicateName {
+                location: DefLocation {
+                    pack_id: BASE_PACK_ID.to_string(),
+                    source_path: "defs/b.json".to_string(),
+                    index: Some(0),
+                    type_name: "Biome",
+                    def_name: "plains".to_string(),
+                },
+                previous_pack_id: BASE_PACK_ID.to_string(),
+                previous_source_path: "defs/a.json".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn conflicts_between_packs_name_both_packs() {
+        let base = parse(r#"{"defs": [{"type": "Biome", "def_name": "plains"}]}"#);
+        let volcano = parse(r#"{"defs": [{"type": "Biome", "def_name": "plains"}]}"#);
+        let sources = vec![
+            source("defs/biomes.json", &base),
+            DefSource {
+                pack_id: "volcano".to_string(),
+                path: "packs/volcano/biomes.json".to_string(),
+                root: &volcano,
+            },
+        ];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        resolve_overrides(app.world.resource(), &sources, &mut errors);
+
+        assert!(matches!(
+            &errors[..],
+            [DefLoadError::DuplicateName { location, previous_pack_id, .. }]
+                if location.pack_id == "volcano" && previous_pack_id == BASE_PACK_ID
+        ));
+    }
+
+    #[test]
+    fn override_replaces_earlier_def() {
+        let first = parse(r#"{"defs": [{"type": "Biome", "def_name": "plains"}]}"#);
+        let second = parse(r#"{"defs": [{"type": "Biome", "def_name": "plains", "override": true, "min_height": 2.0}]}"#);
+        let sources = vec![source("defs/a.json", &first), source("defs/b.json", &second)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let resolved = resolve_overrides(app.world.resource(), &sources, &mut errors);
+
+        assert!(errors.is_empty());
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].location.source_path, "defs/b.json");
+        assert_eq!(resolved[0].entry.fields["min_height"], 2.0);
+    }
+
+    #[test]
+    fn override_patches_the_fields_of_the_earlier_def() {
+        let first = parse(r#"{"defs": [{"type": "Biome", "def_name": "plains", "min_height": 1.0, "max_height": 5.0}]}"#);
+        let second = parse(r#"{"defs": [{"type": "Biome", "def_name": "plains", "override": true, "min_height": 2.0}]}"#);
+        let sources = vec![source("defs/a.json", &first), source("defs/b.json", &second)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let resolved = resolve_overrides(app.world.resource(), &sources, &mut errors);
+
+        assert!(errors.is_empty());
+        assert_eq!(resolved[0].entry.fields["min_height"], 2.0);
+        assert_eq!(resolved[0].entry.fields["max_height"], 5.0);
+    }
+
+    #[test]
+    fn override_appends_to_the_lists_of_the_earlier_def() {
+        let first = parse(
+            r#"{"defs": [{"type": "MonsterType", "def_name": "fire", "damage_scales": [
+                {"target_type_def_name": "grass", "damage_scale": 2.0}
+            ]}]}"#,
+        );
+        let second = parse(
+            r#"{"defs": [{"type": "MonsterType", "def_name": "fire", "override": true, "append": ["damage_scales"],
+                "damage_scales": [{"target_type_def_name": "ice", "damage_scale": 2.0}]
+            }]}"#,
+        );
+        let sources = vec![source("defs/a.json", &first), source("packs/frost/defs/b.json", &second)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let resolved = resolve_overrides(app.world.resource(), &sources, &mut errors);
+
+        assert!(errors.is_empty());
+        let targets: Vec<&str> = resolved[0].entry.fields["damage_scales"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|scale| scale["target_type_def_name"].as_str().unwrap())
+            .collect();
+        assert_eq!(targets, vec!["grass", "ice"]);
+    }
+
+    #[test]
+    fn same_name_in_different_types_is_not_a_duplicate() {
+        let root = parse(
+            r#"{"defs": [
+                {"type": "Biome", "def_name": "water"},
+                {"type": "MonsterType", "def_name": "water", "damage_scales": []}
+            ]}"#,
+        );
+        let sources = vec![source("defs/a.json", &root)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let resolved = resolve_overrides(app.world.resource(), &sources, &mut errors);
+
+        assert!(errors.is_empty());
+        assert_eq!(resolved.len(), 2);
+    }
+
+    #[test]
+    fn unknown_reference_points_at_field() {
+        let root = parse(
+            r#"{"defs": [
+                {"type": "MonsterType", "def_name": "fire", "damage_scales": []},
+                {"type": "MonsterType", "def_name": "water", "damage_scales": [
+                    {"target_type_def_name": "fire", "damage_scale": 2.0},
+                    {"target_type_def_name": "grass", "damage_scale": 0.5}
+                ]}
+            ]}"#,
+        );
+        let sources = vec![source("defs/types.json", &root)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let entries = resolve_overrides(app.world.resource(), &sources, &mut errors);
+        let resolved = resolve_inheritance(&entries, &mut errors);
+        build_test_databases(&app, &resolved, &mut errors);
+
+        assert_eq!(
+            errors,
+            vec![DefLoadError::UnknownReference {
+                location: DefLocation {
+                    pack_id: BASE_PACK_ID.to_string(),
+                    source_path: "defs/types.json".to_string(),
+                    index: Some(1),
+                    type_name: "MonsterType",
+                    def_name: "water".to_string(),
+                },
+                pointer: "/defs/1/damage_scales/1/target_type_def_name".to_string(),
+                target_type: "MonsterType",
+                target_name: "grass".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_errors_are_reported_with_source() {
+        let root = DefsRoot {
+            defs: Vec::new(),
+            is_single_def: false,
+            parse_error: Some("expected value at line 1 column 1".to_string()),
+        };
+        let sources = vec![source("defs/broken.json", &root)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        resolve_overrides(app.world.resource(), &sources, &mut errors);
+
+        assert!(matches!(&errors[..], [DefLoadError::Parse { source_path, .. }] if source_path == "defs/broken.json"));
+    }
+
+    #[test]
+    fn out_of_range_values_are_reported() {
+        let (databases, errors) = load_test_defs(&format!(
+            r#"{{"defs": [
+                {{"type": "MonsterType", "def_name": "normal", "damage_scales": []}},
+                {},
+                {{"type": "Biome", "def_name": "hills", "min_height": 10, "max_height": 5}}
+            ]}}"#,
+            move_def(json!({"def_name": "tackle", "base_mp_usage": 10, "base_accuracy": 7}))
+        ));
+
+        assert!(databases.get::<MonsterMove>().unwrap().is_empty());
+        assert!(databases.get::<BiomeDefinition>().unwrap().is_empty());
+        let pointers: Vec<&str> = errors
+            .iter()
+            .filter_map(|err| match err {
+                DefLoadError::InvalidValue { pointer, .. } => Some(pointer.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pointers, vec!["/defs/1/base_accuracy", "/defs/2/min_height"]);
+    }
+
+    #[test]
+    fn all_formats_parse_to_the_same_defs() {
+        let json = r#"{"defs": [
+            {"type": "Biome", "def_name": "plains", "min_height": 1.5},
+            {"type": "MonsterType", "def_name": "fire", "damage_scales": [{"target_type_def_name": "fire", "damage_scale": 0.5}]}
+        ]}"#;
+        let ron = r#"(
+            // Comments and trailing commas are allowed
+            defs: [
+                {"type": "Biome", "def_name": "plains", "min_height": 1.5},
+                {"type": "MonsterType", "def_name": "fire", "damage_scales": [{"target_type_def_name": "fire", "damage_scale": 0.5}]},
+            ],
+        )"#;
+        let toml = r#"
+            [[defs]]
+            type = "Biome"
+            def_name = "plains"
+            min_height = 1.5
+
+            [[defs]]
+            type = "MonsterType"
+            def_name = "fire"
+            damage_scales = [{ target_type_def_name = "fire", damage_scale = 0.5 }]
+        "#;
+        let yaml = r#"
+            defs:
+              - type: Biome
+                def_name: plains
+                min_height: 1.5
+              - type: MonsterType
+                def_name: fire
+                damage_scales:
+                  - target_type_def_name: fire
+                    damage_scale: 0.5
+        "#;
+
+        let expected = serde_json::to_value(DefFormat::Json.parse(json.as_bytes(), None).unwrap()).unwrap();
+        for (format, text) in [(DefFormat::Ron, ron), (DefFormat::Toml, toml), (DefFormat::Yaml, yaml)] {
+            let root = format.parse(text.as_bytes(), None).unwrap_or_else(|err| panic!("{:?}: {}", format, err));
+            assert_eq!(serde_json::to_value(root).unwrap(), expected, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn format_is_picked_from_the_file_name() {
+        assert_eq!(DefFormat::from_path(Path::new("defs/moves.json")), Some(DefFormat::Json));
+        assert_eq!(DefFormat::from_path(Path::new("defs/moves.defs.ron")), Some(DefFormat::Ron));
+        assert_eq!(DefFormat::from_path(Path::new("defs/moves.defs.toml")), Some(DefFormat::Toml));
+        assert_eq!(DefFormat::from_path(Path::new("defs/moves.defs.yaml")), Some(DefFormat::Yaml));
+        assert_eq!(DefFormat::from_path(Path::new("defs/scene.scn.ron")), None);
+    }
+
+    #[test]
+    fn single_def_files_take_their_name_from_the_file() {
+        let path = Path::new("defs/biomes/coast/beach.defs.yaml");
+        let root = parse_defs_root(b"type: Biome\nmin_height: 0.5\n", path);
+
+        assert!(root.parse_error.is_none(), "{:?}", root.parse_error);
+        assert!(root.is_single_def);
+        assert_eq!(root.defs.len(), 1);
+        assert_eq!(root.defs[0].def_name, "beach");
+    }
+
+    #[test]
+    fn single_def_files_can_set_their_own_name() {
+        let path = Path::new("defs/types/fire.json");
+        let root = parse_defs_root(br#"{"type": "MonsterType", "def_name": "flame", "damage_scales": []}"#, path);
+
+        assert!(root.is_single_def);
+        assert_eq!(root.defs[0].def_name, "flame");
+    }
+
+    #[test]
+    fn single_def_pointers_are_relative_to_the_file() {
+        let root = parse_defs_root(br#"{"type": "Biome", "min_height": 10, "max_height": 5}"#, Path::new("defs/hills.json"));
+        let sources = vec![source("defs/hills.json", &root)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let entries = resolve_overrides(app.world.resource(), &sources, &mut errors);
+        let resolved = resolve_inheritance(&entries, &mut errors);
+        build_test_databases(&app, &resolved, &mut errors);
+
+        assert!(matches!(
+            &errors[..],
+            [DefLoadError::InvalidValue { location, pointer, .. }] if location.index.is_none() && pointer == "/min_height"
+        ));
+    }
+
+    #[test]
+    fn def_name_is_the_file_name_without_extension() {
+        assert_eq!(def_name_from_path(Path::new("defs/monsters/fire/emberpup.json")), Some("emberpup"));
+        assert_eq!(def_name_from_path(Path::new("defs/monsters/emberpup.defs.ron")), Some("emberpup"));
+        assert_eq!(def_name_from_path(Path::new("defs/monsters/emberpup.ron")), None);
+    }
+
+    fn tackle_with_parent(parent: &str) -> String {
+        format!(
+            r#"{{"type": "MonsterMove", "def_name": "tackle", "parent": "{}", "base_damage": 40}}"#,
+            parent
+        )
+    }
+
+    #[test]
+    fn defs_inherit_fields_from_abstract_parents() {
+        let (databases, errors) = load_test_defs(&format!(
+            r#"{{"defs": [
+                {{"type": "MonsterType", "def_name": "normal", "damage_scales": []}},
+                {},
+                {}
+            ]}}"#,
+            move_def(json!({
+                "def_name": "base_physical",
+                "abstract": true,
+                "base_damage": 10,
+                "base_accuracy": 0.95,
+                "base_crit_multiplier": 1.5
+            })),
+            tackle_with_parent("base_physical")
+        ));
+        let moves = databases.get::<MonsterMove>().unwrap();
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(!moves.contains("base_physical"));
+        let tackle = moves.get_by_name("tackle").unwrap();
+        assert_eq!(tackle.base_damage, 40.0);
+        assert_eq!(tackle.base_accuracy, 0.95);
+        assert_eq!(tackle.category, MoveCategory::Physical);
+    }
+
+    #[test]
+    fn appended_lists_extend_the_parent_list() {
+        let root = parse(
+            r#"{"defs": [
+                {
+                    "type": "Monster",
+                    "def_name": "base_pup",
+                    "abstract": true,
+                    "model_path": "monsters/001/model.glb#Scene0",
+                    "types": ["normal"],
+                    "spawn_locations": [{"biome_def": "plains"}],
+                    "move_learn_set": [{"monster_move_def_name": "tackle", "conditions": [{"Level": 0}]}]
+                },
+                {
+                    "type": "Monster",
+                    "def_name": "emberpup",
+                    "parent": "base_pup",
+                    "append": ["move_learn_set"],
+                    "types": ["fire"],
+                    "move_learn_set": [{"monster_move_def_name": "ember", "conditions": [{"Level": 5}]}]
+                }
+            ]}"#,
+        );
+        let sources = vec![source("defs/monsters.json", &root)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let entries = resolve_overrides(app.world.resource(), &sources, &mut errors);
+        let resolved = resolve_inheritance(&entries, &mut errors);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(resolved.len(), 1);
+        let emberpup = &resolved[0].fields;
+        assert_eq!(emberpup["types"], serde_json::json!(["fire"]));
+        let learned: Vec<&str> = emberpup["move_learn_set"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|learned| learned["monster_move_def_name"].as_str().unwrap())
+            .collect();
+        assert_eq!(learned, vec!["tackle", "ember"]);
+    }
+
+    #[test]
+    fn inheritance_cycles_are_reported() {
+        let root = parse(
+            r#"{"defs": [
+                {"type": "Biome", "def_name": "a", "parent": "b"},
+                {"type": "Biome", "def_name": "b", "parent": "a"},
+                {"type": "Biome", "def_name": "c", "parent": "a"}
+            ]}"#,
+        );
+        let sources = vec![source("defs/biomes.json", &root)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let entries = resolve_overrides(app.world.resource(), &sources, &mut errors);
+        let resolved = resolve_inheritance(&entries, &mut errors);
+
+        assert!(resolved.is_empty());
+        let cycles: Vec<Vec<String>> = errors
+            .iter()
+            .filter_map(|err| match err {
+                DefLoadError::InheritanceCycle { cycle, .. } => Some(cycle.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(cycles, vec![vec!["a", "b", "a"], vec!["b", "a", "b"]]);
+    }
+
+    #[test]
+    fn unknown_parent_points_at_parent_field() {
+        let root = parse(&format!(r#"{{"defs": [{}]}}"#, tackle_with_parent("base_special")));
+        let sources = vec![source("defs/moves.json", &root)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let entries = resolve_overrides(app.world.resource(), &sources, &mut errors);
+        resolve_inheritance(&entries, &mut errors);
+
+        assert!(matches!(
+            &errors[..],
+            [DefLoadError::UnknownReference { pointer, target_name, .. }] if pointer == "/defs/0/parent" && target_name == "base_special"
+        ));
+    }
+
+    #[test]
+    fn unknown_types_point_at_type_field() {
+        let root = parse(r#"{"defs": [{"type": "Biome", "def_name": "plains"}, {"type": "Item", "def_name": "potion"}]}"#);
+        let sources = vec![source("defs/items.json", &root)];
+        let app = test_app();
+        let mut errors = Vec::new();
+
+        let resolved = resolve_overrides(app.world.resource(), &sources, &mut errors);
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(
+            errors,
+            vec![DefLoadError::UnknownType {
+                source_path: "defs/items.json".to_string(),
+                pointer: "/defs/1/type".to_string(),
+                type_name: "Item".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn defs_can_refer_to_defs_in_any_order() {
+        let (databases, errors) = load_test_defs(&format!(
+            r#"{{"defs": [
+                {},
+                {{"type": "MonsterType", "def_name": "fire", "damage_scales": [
+                    {{"target_type_def_name": "fire", "damage_scale": 0.5}},
+                    {{"target_type_def_name": "water", "damage_scale": 0.5}}
+                ]}},
+                {{"type": "MonsterType", "def_name": "water", "damage_scales": []}}
+            ]}}"#,
+            move_def(json!({
+                "def_name": "ember",
+                "move_type_def_name": "fire",
+                "base_mp_usage": 5,
+                "category": "Magical"
+            }))
+        ));
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        let ember = databases.get::<MonsterMove>().unwrap().get_by_name("ember").unwrap();
+        assert_eq!(ember.move_type, DefId::<MonsterType>::from_def_name("fire"));
+        let fire = databases.get::<MonsterType>().unwrap().get_by_name("fire").unwrap();
+        let targets: Vec<DefId<MonsterType>> = fire.damage_scales.iter().map(|scale| scale.target_type).collect();
+        assert_eq!(
+            targets,
+            vec![DefId::from_def_name("fire"), DefId::from_def_name("water")]
+        );
+    }
+
+    #[test]
+    fn defs_referring_to_failed_defs_are_left_out() {
+        let (databases, errors) = load_test_defs(&format!(
+            r#"{{"defs": [
+                {{"type": "MonsterType", "def_name": "normal", "damage_scales": [{{"target_type_def_name": "normal", "damage_scale": -1}}]}},
+                {}
+            ]}}"#,
+            move_def(json!({"def_name": "tackle"}))
+        ));
+
+        assert!(databases.get::<MonsterType>().unwrap().is_empty());
+        assert!(databases.get::<MonsterMove>().unwrap().is_empty());
+        let pointers: Vec<&str> = errors
+            .iter()
+            .filter_map(|err| match err {
+                DefLoadError::InvalidValue { pointer, .. } => Some(pointer.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            pointers,
+            vec!["/defs/0/damage_scales/0/damage_scale", "/defs/1/move_type_def_name"]
+        );
+    }
+
+    #[test]
+    fn missing_models_are_reported_at_their_field() {
+        let (databases, errors) = load_test_defs(&format!(
+            r#"{{"defs": [
+                {{"type": "MonsterType", "def_name": "normal", "damage_scales": []}},
+                {}
+            ]}}"#,
+            monster_def(json!({"def_name": "ghostpup", "model_path": "monsters/404/model.glb#Scene0"}))
+        ));
+
+        assert!(databases.get::<MonsterDefinition>().unwrap().is_empty());
+        assert!(databases.asset_references.is_empty());
+        match errors.as_slice() {
+            [DefLoadError::InvalidAsset { pointer, path, .. }] => {
+                assert_eq!(pointer, "/defs/1/model_path");
+                assert_eq!(path, "monsters/404/model.glb#Scene0");
+            }
+            _ => panic!("expected a single invalid asset error, got {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn existing_models_are_left_to_validate_once_loaded() {
+        let (databases, errors) = load_test_defs(&format!(
+            r#"{{"defs": [
+                {{"type": "MonsterType", "def_name": "normal", "damage_scales": []}},
+                {}
+            ]}}"#,
+            monster_def(json!({"def_name": "block"}))
+        ));
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        let paths: Vec<(&str, &str)> = databases
+            .asset_references
+            .iter()
+            .map(|reference| (reference.pointer.as_str(), reference.path.as_str()))
+            .collect();
+        assert_eq!(paths, vec![("/defs/1/model_path", "monsters/001/model.glb#Scene0")]);
+    }
+
+    #[test]
+    fn learn_conditions_are_checked_inside_groups() {
+        let (databases, errors) = load_test_defs(&format!(
+            r#"{{"defs": [
+                {{"type": "MonsterType", "def_name": "normal", "damage_scales": []}},
+                {},
+                {{"type": "Item", "def_name": "tackle_manual"}},
+                {}
+            ]}}"#,
+            move_def(json!({"def_name": "tackle"})),
+            monster_def(json!({
+                "def_name": "block",
+                "move_learn_set": [{
+                    "monster_move_def_name": "tackle",
+                    "conditions": [{"AnyOf": [{"TeachingItem": "tackle_manual"}, {"AllOf": [
+                        {"Level": 10},
+                        {"InBiome": "volcano"}
+                    ]}]}]
+                }]
+            }))
+        ));
+
+        assert!(databases.get::<MonsterDefinition>().unwrap().is_empty());
+        match errors.as_slice() {
+            [DefLoadError::UnknownReference {
+                pointer,
+                target_type,
+                target_name,
+                ..
+            }] => {
+                assert_eq!(pointer, "/defs/3/move_learn_set/0/conditions/0/AnyOf/1/AllOf/1/InBiome");
+                assert_eq!(*target_type, "Biome");
+                assert_eq!(target_name, "volcano");
+            }
+            _ => panic!("expected a single unknown reference, got {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn empty_learn_condition_groups_are_rejected() {
+        let learned = |def_name: &str, group: &str| {
+            json!({
+                "def_name": def_name,
+                "move_learn_set": [{"monster_move_def_name": "tackle", "conditions": [{group: []}]}]
+            })
+        };
+        let (databases, errors) = load_test_defs(&format!(
+            r#"{{"defs": [
+                {{"type": "MonsterType", "def_name": "normal", "damage_scales": []}},
+                {},
+                {},
+                {}
+            ]}}"#,
+            move_def(json!({"def_name": "tackle"})),
+            monster_def(learned("block", "AllOf")),
+            monster_def(learned("brick", "AnyOf"))
+        ));
+
+        assert!(databases.get::<MonsterDefinition>().unwrap().is_empty());
+        let pointers: Vec<&str> = errors
+            .iter()
+            .filter_map(|err| match err {
+                DefLoadError::InvalidValue { pointer, .. } => Some(pointer.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            pointers,
+            vec!["/defs/2/move_learn_set/0/conditions/0/AllOf", "/defs/3/move_learn_set/0/conditions/0/AnyOf"]
+        );
+    }
+
+    #[test]
+    fn evolution_cycles_fail_every_monster_in_them() {
+        let evolution =
+            |target: &str, condition: Value| json!([{"target_def_name": target, "conditions": [condition]}]);
+        let (databases, errors) = load_test_defs(&format!(
+            r#"{{"defs": [
+                {{"type": "MonsterType", "def_name": "normal", "damage_scales": []}},
+                {},
+                {},
+                {},
+                {}
+            ]}}"#,
+            monster_def(json!({"def_name": "pebble", "evolutions": evolution("block", json!({"Level": 10}))})),
+            monster_def(json!({"def_name": "block", "evolutions": evolution("brick", json!({"TimeOfDay": "Night"}))})),
+            monster_def(json!({"def_name": "brick", "evolutions": evolution("block", json!({"Level": 30}))})),
+            monster_def(json!({"def_name": "boulder"}))
+        ));
+
+        let monsters = databases.get::<MonsterDefinition>().unwrap();
+        assert_eq!(monsters.len(), 1);
+        assert!(monsters.get_by_name("boulder").is_some());
+        let messages: Vec<(&str, &str)> = errors
+            .iter()
+            .filter_map(|err| match err {
+                DefLoadError::InvalidValue { pointer, message, .. } => Some((pointer.as_str(), message.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    "/defs/2/evolutions/0/target_def_name",
+                    "evolves back into itself through block -> brick -> block"
+                ),
+                (
+                    "/defs/3/evolutions/0/target_def_name",
+                    "evolves back into itself through brick -> block -> brick"
+                ),
+                (
+                    "/defs/1/evolutions/0/target_def_name",
+                    "refers to Monster def 'block', which failed to load"
+                ),
+            ]
+        );
+    }
 }
@@ -0,0 +1,337 @@
+use crate::prelude::*;
+use bevy::asset::{AssetLoader, Error, LoadContext, LoadedAsset};
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// The folder in the assets with a string table per locale, like `locale/en.locale.json`.
+pub const LOCALE_FOLDER: &str = "locale";
+/// The locale every fallback chain ends in, which is expected to have all strings.
+pub const DEFAULT_LOCALE: &str = "en";
+const LOCALE_TABLE_EXTENSION: &str = "locale.json";
+
+/// The player facing name of a def.
+pub const NAME_FIELD: &str = "name";
+/// The player facing description of a def.
+pub const DESCRIPTION_FIELD: &str = "description";
+/// The fields every def is expected to have a translation for.
+const DEF_FIELDS: &[&str] = &[NAME_FIELD, DESCRIPTION_FIELD];
+
+/// The strings of one locale, as a flat object of keys like `Monster.block.name` to the translated text.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, TypeUuid)]
+#[uuid = "b3e1c7a4-5d2f-4e8b-8a61-0c9f2d7e4b15"]
+#[serde(transparent)]
+pub struct LocaleTable {
+    pub strings: BTreeMap<String, String>,
+    /// Set by the loader when the table could not be parsed.
+    #[serde(skip)]
+    pub parse_error: Option<String>,
+}
+
+impl LocaleTable {
+    /// Parses a table. A broken table still produces an empty table, so the loading state does not stall.
+    pub fn parse(bytes: &[u8]) -> Self {
+        serde_json::from_slice(bytes).unwrap_or_else(|err: serde_json::Error| LocaleTable {
+            strings: BTreeMap::new(),
+            parse_error: Some(err.to_string()),
+        })
+    }
+}
+
+pub struct LocaleTableLoader;
+
+impl AssetLoader for LocaleTableLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(LocaleTable::parse(bytes)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        // Picked over the json def loader, as the most specific extension wins.
+        &[LOCALE_TABLE_EXTENSION]
+    }
+}
+
+/// The locale a table is for, like `en-GB` for `locale/en-GB.locale.json`.
+pub fn locale_id_from_path(path: &str) -> Option<&str> {
+    let file_name = path.rsplit('/').next()?;
+    file_name.strip_suffix(LOCALE_TABLE_EXTENSION)?.strip_suffix('.')
+}
+
+/// The key of a translated field of a def, like `Monster.block.name`.
+pub fn def_string_key(type_name: &str, def_name: &str, field: &str) -> String {
+    format!("{}.{}.{}", type_name, def_name, field)
+}
+
+/// The locale the game is shown in, along with the locales to fall back on for missing strings.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    chain: Vec<String>,
+}
+
+impl Locale {
+    /// Falls back from a regional locale to its language, and from there to [`DEFAULT_LOCALE`],
+    /// like `de-AT`, `de`, `en`.
+    pub fn new(id: &str) -> Self {
+        let mut chain: Vec<String> = Vec::new();
+        let mut current = id;
+        loop {
+            chain.push(current.to_string());
+            match current.rfind('-') {
+                Some(end) => current = &current[..end],
+                None => break,
+            }
+        }
+        if !chain.iter().any(|locale| locale == DEFAULT_LOCALE) {
+            chain.push(DEFAULT_LOCALE.to_string());
+        }
+        Locale { chain }
+    }
+
+    /// The locales to look strings up in, in order.
+    pub fn chain(&self) -> &[String] {
+        &self.chain
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::new(DEFAULT_LOCALE)
+    }
+}
+
+/// The string tables of all locales, by locale id.
+#[derive(Resource, Debug, Default, Clone, Deserialize, Serialize)]
+pub struct LocalizedStrings {
+    tables: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl LocalizedStrings {
+    /// Collects the tables, given as `(path, table)` pairs. Tables that failed to parse are logged and left out.
+    pub fn from_tables<'a>(tables: impl IntoIterator<Item = (String, &'a LocaleTable)>) -> Self {
+        let mut strings = LocalizedStrings::default();
+        for (path, table) in tables {
+            if let Some(message) = &table.parse_error {
+                error!("Failed to parse locale table '{}': {}", path, message);
+                continue;
+            }
+            let Some(locale) = locale_id_from_path(&path) else {
+                warn!("Locale table '{}' is not named like <locale>.{}", path, LOCALE_TABLE_EXTENSION);
+                continue;
+            };
+            strings
+                .tables
+                .entry(locale.to_string())
+                .or_default()
+                .extend(table.strings.iter().map(|(key, value)| (key.clone(), value.clone())));
+        }
+        strings
+    }
+
+    /// Looks up a string in the locale, falling back along its chain.
+    pub fn get(&self, locale: &Locale, key: &str) -> Option<&str> {
+        locale
+            .chain()
+            .iter()
+            .find_map(|id| self.tables.get(id)?.get(key))
+            .map(String::as_str)
+    }
+
+    /// The display name of a def, or its `def_name` when no locale in the chain has one.
+    pub fn def_name<'a, T: Definition>(&'a self, locale: &Locale, def: &'a T) -> &'a str {
+        self.get(locale, &def_string_key(T::TYPE_NAME, def.get_def_name(), NAME_FIELD))
+            .unwrap_or_else(|| def.get_def_name())
+    }
+
+    pub fn def_description<T: Definition>(&self, locale: &Locale, def: &T) -> Option<&str> {
+        self.get(locale, &def_string_key(T::TYPE_NAME, def.get_def_name(), DESCRIPTION_FIELD))
+    }
+
+    /// Lists the def strings each locale does not have itself, ignoring fallbacks.
+    ///
+    /// `defs` are the `(type name, def name)` pairs of all loaded defs.
+    pub fn find_missing_translations(&self, defs: &[(&'static str, String)]) -> TranslationReport {
+        let mut missing = Vec::new();
+        for (locale, table) in self.tables.iter() {
+            for (type_name, def_name) in defs.iter() {
+                for field in DEF_FIELDS.iter().copied() {
+                    if !table.contains_key(&def_string_key(type_name, def_name, field)) {
+                        missing.push(MissingTranslation {
+                            locale: locale.clone(),
+                            type_name: *type_name,
+                            def_name: def_name.clone(),
+                            field,
+                        });
+                    }
+                }
+            }
+        }
+        TranslationReport { missing }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingTranslation {
+    pub locale: String,
+    pub type_name: &'static str,
+    pub def_name: String,
+    pub field: &'static str,
+}
+
+impl Display for MissingTranslation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} def '{}' has no {} ('{}')",
+            self.locale,
+            self.type_name,
+            self.def_name,
+            self.field,
+            def_string_key(self.type_name, &self.def_name, self.field)
+        )
+    }
+}
+
+/// The translations missing after the last def load. Unlike def errors, these never stop the game from loading.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct TranslationReport {
+    pub missing: Vec<MissingTranslation>,
+}
+
+impl TranslationReport {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl Display for TranslationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.missing.is_empty() {
+            return write!(f, "All definitions are translated");
+        }
+
+        write!(f, "{} missing translation(s):", self.missing.len())?;
+        for missing in self.missing.iter() {
+            write!(f, "\n  - {}", missing)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the locale tables straight from disk, as `(asset path, table)` pairs, for loading without an [`App`].
+///
+/// The locale folder is optional, so a missing folder gives no tables.
+pub fn read_locale_tables(asset_folder: &Path) -> Vec<(String, LocaleTable)> {
+    let Ok(entries) = std::fs::read_dir(asset_folder.join(LOCALE_FOLDER)) else {
+        return Vec::new();
+    };
+
+    let mut tables = Vec::new();
+    for entry in entries.flatten() {
+        let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if locale_id_from_path(&file_name).is_none() {
+            continue;
+        }
+        let table = match std::fs::read(entry.path()) {
+            Ok(bytes) => LocaleTable::parse(&bytes),
+            Err(err) => LocaleTable {
+                strings: BTreeMap::new(),
+                parse_error: Some(err.to_string()),
+            },
+        };
+        tables.push((format!("{}/{}", LOCALE_FOLDER, file_name), table));
+    }
+    tables.sort_by(|(a, _), (b, _)| a.cmp(b));
+    tables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::BiomeDefinition;
+
+    fn table(json: &str) -> LocaleTable {
+        let table = LocaleTable::parse(json.as_bytes());
+        assert!(table.parse_error.is_none(), "{:?}", table.parse_error);
+        table
+    }
+
+    fn plains() -> BiomeDefinition {
+        BiomeDefinition {
+            def_name: "plains".to_string(),
+            min_height: None,
+            max_height: None,
+            min_humidity: None,
+            max_humidity: None,
+            min_temperature: None,
+            max_temperature: None,
+        }
+    }
+
+    #[test]
+    fn regional_locales_fall_back_to_language_and_default() {
+        assert_eq!(Locale::new("de-AT").chain(), ["de-AT", "de", "en"]);
+        assert_eq!(Locale::new("en-GB").chain(), ["en-GB", "en"]);
+        assert_eq!(Locale::default().chain(), ["en"]);
+    }
+
+    #[test]
+    fn strings_are_looked_up_along_the_chain() {
+        let en = table(r#"{"Biome.plains.name": "Plains", "Biome.plains.description": "Wide and grassy."}"#);
+        let de = table(r#"{"Biome.plains.name": "Ebene"}"#);
+        let strings = LocalizedStrings::from_tables(vec![
+            ("locale/en.locale.json".to_string(), &en),
+            ("locale/de.locale.json".to_string(), &de),
+        ]);
+        let locale = Locale::new("de-AT");
+
+        assert_eq!(strings.def_name(&locale, &plains()), "Ebene");
+        assert_eq!(strings.def_description(&locale, &plains()), Some("Wide and grassy."));
+    }
+
+    #[test]
+    fn defs_without_a_name_show_their_def_name() {
+        let strings = LocalizedStrings::default();
+
+        assert_eq!(strings.def_name(&Locale::default(), &plains()), "plains");
+    }
+
+    #[test]
+    fn missing_translations_are_reported_per_locale() {
+        let en = table(r#"{"Biome.plains.name": "Plains", "Biome.plains.description": "Wide and grassy."}"#);
+        let de = table(r#"{"Biome.plains.name": "Ebene"}"#);
+        let strings = LocalizedStrings::from_tables(vec![
+            ("locale/en.locale.json".to_string(), &en),
+            ("locale/de.locale.json".to_string(), &de),
+        ]);
+
+        let report = strings.find_missing_translations(&[("Biome", "plains".to_string())]);
+
+        assert_eq!(
+            report.missing,
+            vec![MissingTranslation {
+                locale: "de".to_string(),
+                type_name: "Biome",
+                def_name: "plains".to_string(),
+                field: DESCRIPTION_FIELD,
+            }]
+        );
+    }
+
+    #[test]
+    fn locale_id_is_the_file_name_without_extension() {
+        assert_eq!(locale_id_from_path("locale/en-GB.locale.json"), Some("en-GB"));
+        assert_eq!(locale_id_from_path("locale/en.json"), None);
+    }
+}
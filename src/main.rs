@@ -1,17 +1,30 @@
 use bevy_editor_pls::EditorWindowPlacement;
 use crate::ground::GroundPlugin;
-use crate::json_asset_definition::{output_json_schema, DefPlugin};
+use crate::items::ItemPlugin;
+use crate::def_report_window::DefLoadReportWindow;
+use crate::json_asset_definition::{DefLoadStrictness, DefPlugin};
 use crate::jumping::*;
 use crate::monsters::MonsterPlugin;
 use crate::player::PlayerPlugin;
 use crate::prelude::*;
+use crate::world::WorldPlugin;
 use bevy_editor_pls::prelude::*;
 
+mod cli;
+mod def_cache;
 mod def_database;
+mod def_diff;
+mod def_errors;
+mod def_packs;
+mod def_registry;
+mod def_report_window;
+mod def_schema;
 mod def_types;
 mod ground;
+mod items;
 mod json_asset_definition;
 mod jumping;
+mod localization;
 mod monsters;
 mod player;
 mod prelude;
@@ -19,7 +32,9 @@ mod shared;
 mod world;
 
 fn main() {
-    output_json_schema();
+    if let Some(exit_code) = cli::run_command() {
+        std::process::exit(exit_code);
+    }
 
     App::new()
         .add_plugins(DefaultPlugins.set(AssetPlugin {
@@ -32,6 +47,7 @@ fn main() {
                 ..Default::default()
             }),
         })
+        .add_editor_window::<DefLoadReportWindow>()
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
         .add_plugin(RapierDebugRenderPlugin {
             always_on_top: true,
@@ -39,11 +55,16 @@ fn main() {
             ..default()
         })
         .add_state::<GameState>()
-        .add_loading_state(LoadingState::new(GameState::LoadingFromDisk).continue_to_state(GameState::AddingToDatabase))
+        .add_loading_state(LoadingState::new(GameState::LoadingFromDisk).continue_to_state(GameState::CheckingDefCache))
+        .add_loading_state(LoadingState::new(GameState::LoadingDefs).continue_to_state(GameState::AddingToDatabase))
         .add_plugin(GroundPlugin)
         .add_plugin(PlayerPlugin)
         .add_plugin(JumpingPlugin)
         .add_plugin(MonsterPlugin)
-        .add_plugin(DefPlugin)
+        .add_plugin(ItemPlugin)
+        .add_plugin(WorldPlugin)
+        .add_plugin(DefPlugin {
+            strictness: DefLoadStrictness::from_args(),
+        })
         .run();
 }
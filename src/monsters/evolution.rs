@@ -0,0 +1,408 @@
+use crate::def_errors::DefFieldError;
+use crate::def_registry::LinkContext;
+use crate::items::ItemDefinition;
+use crate::monsters::{Monster, MonsterDefinition, MonsterLevelledUp, MonsterMove, MoveLearnOffered};
+use crate::prelude::*;
+use crate::world::{BiomeDefinition, TimeOfDay};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RawEvolution {
+    pub target_def_name: String,
+    pub conditions: Vec<RawEvolutionCondition>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub enum RawEvolutionCondition {
+    Level(u32),
+    Item(String),
+    InBiome(String),
+    TimeOfDay(TimeOfDay),
+    MinBond(u32),
+}
+
+impl RawEvolution {
+    /// Links the evolution, where `pointer` points at the evolution.
+    pub fn link(&self, context: &LinkContext, pointer: &str) -> Result<Evolution, DefFieldError> {
+        let target =
+            context.resolve::<MonsterDefinition>(&self.target_def_name, format!("{}/target_def_name", pointer))?;
+        let mut conditions = Vec::new();
+        for (i, condition) in self.conditions.iter().enumerate() {
+            let pointer = format!("{}/conditions/{}", pointer, i);
+            let condition = match condition {
+                RawEvolutionCondition::Level(level) => EvolutionCondition::Level(*level),
+                RawEvolutionCondition::Item(item) => {
+                    EvolutionCondition::Item(context.resolve::<ItemDefinition>(item, format!("{}/Item", pointer))?)
+                }
+                RawEvolutionCondition::InBiome(biome) => {
+                    EvolutionCondition::InBiome(context.resolve::<BiomeDefinition>(biome, format!("{}/InBiome", pointer))?)
+                }
+                RawEvolutionCondition::TimeOfDay(time_of_day) => EvolutionCondition::TimeOfDay(*time_of_day),
+                RawEvolutionCondition::MinBond(bond) => EvolutionCondition::MinBond(*bond),
+            };
+            conditions.push(condition);
+        }
+        Ok(Evolution { target, conditions })
+    }
+}
+
+/// A monster a monster can evolve into, once all conditions are met.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Evolution {
+    pub target: DefId<MonsterDefinition>,
+    pub conditions: Vec<EvolutionCondition>,
+}
+
+impl Evolution {
+    pub fn is_possible(&self, context: &EvolutionContext) -> bool {
+        self.conditions.iter().all(|condition| condition.is_met(context))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum EvolutionCondition {
+    /// The monster has reached the level.
+    Level(u32),
+    /// The item is used on the monster.
+    Item(DefId<ItemDefinition>),
+    /// The monster is in the biome.
+    InBiome(DefId<BiomeDefinition>),
+    /// It is this time of day.
+    TimeOfDay(TimeOfDay),
+    /// The bond between the monster and its tamer is at least this strong.
+    MinBond(u32),
+}
+
+impl EvolutionCondition {
+    pub fn is_met(&self, context: &EvolutionContext) -> bool {
+        match self {
+            EvolutionCondition::Level(required) => context.level >= *required,
+            EvolutionCondition::Item(item) => context.item == Some(*item),
+            EvolutionCondition::InBiome(biome) => context.biome == Some(*biome),
+            EvolutionCondition::TimeOfDay(time_of_day) => context.time_of_day == *time_of_day,
+            EvolutionCondition::MinBond(required) => context.bond >= *required,
+        }
+    }
+}
+
+/// The state of a monster and the world that evolution conditions are checked against.
+#[derive(Debug, Clone, Copy)]
+pub struct EvolutionContext {
+    pub level: u32,
+    pub bond: u32,
+    /// The item used on the monster, or `None` when it is checked after levelling up.
+    pub item: Option<DefId<ItemDefinition>>,
+    /// The biome the monster is in, if it is in one.
+    pub biome: Option<DefId<BiomeDefinition>>,
+    pub time_of_day: TimeOfDay,
+}
+
+/// Finds the monsters that evolve back into themselves, pointing at the evolution that starts the cycle.
+///
+/// Only the first cycle of each monster is reported, as failing the monster breaks its other cycles too.
+pub fn find_evolution_cycles(monsters: &[&MonsterDefinition]) -> Vec<(String, DefFieldError)> {
+    let by_id: HashMap<DefId<MonsterDefinition>, &MonsterDefinition> = monsters
+        .iter()
+        .map(|monster| (DefId::from_def_name(&monster.def_name), *monster))
+        .collect();
+
+    let mut cycles = Vec::new();
+    for monster in monsters {
+        let start = DefId::from_def_name(&monster.def_name);
+        for (i, evolution) in monster.evolutions.iter().enumerate() {
+            let Some(path) = find_evolution_path(evolution.target, start, &by_id, &mut HashSet::new()) else {
+                continue;
+            };
+            let mut cycle = vec![monster.def_name.clone()];
+            cycle.extend(path);
+            cycles.push((
+                monster.def_name.clone(),
+                DefFieldError::InvalidValue {
+                    pointer: format!("/evolutions/{}/target_def_name", i),
+                    message: format!("evolves back into itself through {}", cycle.join(" -> ")),
+                },
+            ));
+            break;
+        }
+    }
+    cycles
+}
+
+/// The names of the monsters on an evolution path from `from` to `to`, including both.
+fn find_evolution_path(
+    from: DefId<MonsterDefinition>,
+    to: DefId<MonsterDefinition>,
+    by_id: &HashMap<DefId<MonsterDefinition>, &MonsterDefinition>,
+    visited: &mut HashSet<DefId<MonsterDefinition>>,
+) -> Option<Vec<String>> {
+    let monster = by_id.get(&from)?;
+    if from == to {
+        return Some(vec![monster.def_name.clone()]);
+    }
+    if !visited.insert(from) {
+        return None;
+    }
+
+    monster.evolutions.iter().find_map(|evolution| {
+        let mut path = find_evolution_path(evolution.target, to, by_id, visited)?;
+        path.insert(0, monster.def_name.clone());
+        Some(path)
+    })
+}
+
+/// Sent when a monster evolved, after its def has been changed to the monster it evolved into.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonsterEvolved {
+    pub monster: Entity,
+    pub from: DefId<MonsterDefinition>,
+    pub into: DefId<MonsterDefinition>,
+}
+
+/// Send to use an item on a monster, which teaches it the moves that need the item, and evolves it when the item
+/// meets the conditions of one of its evolutions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemUsed {
+    pub monster: Entity,
+    pub item: DefId<ItemDefinition>,
+    /// The biome the monster is in, for moves that can only be learned and monsters that can only evolve there.
+    pub biome: Option<DefId<BiomeDefinition>>,
+}
+
+/// Evolves the monsters that levelled up or had an item used on them, once the conditions of one of their
+/// evolutions are met. Used items also teach the moves that need them, before the monster evolves.
+pub fn evolve_monsters(
+    mut levelled_up_events: EventReader<MonsterLevelledUp>,
+    mut item_events: EventReader<ItemUsed>,
+    mut evolved_events: EventWriter<MonsterEvolved>,
+    mut offered_events: EventWriter<MoveLearnOffered>,
+    mut monsters: Query<&mut Monster>,
+    monster_defs: Res<DefDatabase<MonsterDefinition>>,
+    moves: Res<DefDatabase<MonsterMove>>,
+    time_of_day: Res<TimeOfDay>,
+) {
+    let levelled_up = levelled_up_events.iter().map(|levelled_up| (levelled_up.monster, None, levelled_up.biome));
+    let items_used = item_events.iter().map(|used| (used.monster, Some(used.item), used.biome));
+    for (entity, item, biome) in levelled_up.chain(items_used) {
+        let Ok(mut monster) = monsters.get_mut(entity) else {
+            continue;
+        };
+        let Some(def) = monster_defs.get_by_id(&monster.instance.def) else {
+            warn!("Monster {:?} has no def, so it cannot evolve", entity);
+            continue;
+        };
+
+        let mut offered = match item {
+            Some(item) => monster.instance.use_teaching_item(item, def, &moves, biome),
+            None => Vec::new(),
+        };
+        let from = monster.instance.def;
+        if let Some(evolution_offered) = monster.instance.try_evolve(&monster_defs, &moves, item, biome, *time_of_day) {
+            evolved_events.send(MonsterEvolved {
+                monster: entity,
+                from,
+                into: monster.instance.def,
+            });
+            offered.extend(evolution_offered);
+        }
+
+        for monster_move in offered {
+            offered_events.send(MoveLearnOffered {
+                monster: entity,
+                monster_move,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monsters::test_fixtures::{app_with_monster, block, evolving_defs, gain_levels};
+    use crate::monsters::MoveLearnCondition;
+    use bevy::ecs::event::Events;
+
+    fn monster(def_name: &str, evolves_into: &[&str]) -> MonsterDefinition {
+        MonsterDefinition {
+            def_name: def_name.to_string(),
+            evolutions: evolves_into
+                .iter()
+                .map(|target| Evolution {
+                    target: DefId::from_def_name(target),
+                    conditions: Vec::new(),
+                })
+                .collect(),
+            ..block()
+        }
+    }
+
+    fn cycle_errors(monsters: &[MonsterDefinition]) -> Vec<(String, String, String)> {
+        let monsters: Vec<&MonsterDefinition> = monsters.iter().collect();
+        find_evolution_cycles(&monsters)
+            .into_iter()
+            .map(|(def_name, err)| match err {
+                DefFieldError::InvalidValue { pointer, message } => (def_name, pointer, message),
+                err => panic!("expected an invalid value, got {:?}", err),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn conditions_check_the_monster_and_the_world() {
+        let context = EvolutionContext {
+            level: 20,
+            bond: 50,
+            item: Some(DefId::from_def_name("moon_stone")),
+            biome: Some(DefId::from_def_name("dunes")),
+            time_of_day: TimeOfDay::Night,
+        };
+
+        assert!(EvolutionCondition::Level(20).is_met(&context));
+        assert!(!EvolutionCondition::Level(21).is_met(&context));
+        assert!(EvolutionCondition::Item(DefId::from_def_name("moon_stone")).is_met(&context));
+        assert!(!EvolutionCondition::Item(DefId::from_def_name("sun_stone")).is_met(&context));
+        assert!(EvolutionCondition::InBiome(DefId::from_def_name("dunes")).is_met(&context));
+        assert!(!EvolutionCondition::TimeOfDay(TimeOfDay::Day).is_met(&context));
+        assert!(EvolutionCondition::MinBond(50).is_met(&context));
+        assert!(!EvolutionCondition::Item(DefId::from_def_name("moon_stone")).is_met(&EvolutionContext {
+            item: None,
+            ..context
+        }));
+    }
+
+    #[test]
+    fn evolution_chains_are_not_cycles() {
+        let monsters = [
+            monster("block", &["brick", "slab"]),
+            monster("brick", &["tower"]),
+            monster("slab", &["tower"]),
+            monster("tower", &[]),
+        ];
+
+        assert!(cycle_errors(&monsters).is_empty());
+    }
+
+    #[test]
+    fn cycles_are_reported_on_every_monster_in_them() {
+        let monsters = [
+            monster("block", &["brick"]),
+            monster("brick", &["tower"]),
+            monster("tower", &["slab", "block"]),
+            monster("slab", &[]),
+        ];
+
+        assert_eq!(
+            cycle_errors(&monsters),
+            vec![
+                (
+                    "block".to_string(),
+                    "/evolutions/0/target_def_name".to_string(),
+                    "evolves back into itself through block -> brick -> tower -> block".to_string()
+                ),
+                (
+                    "brick".to_string(),
+                    "/evolutions/0/target_def_name".to_string(),
+                    "evolves back into itself through brick -> tower -> block -> brick".to_string()
+                ),
+                (
+                    "tower".to_string(),
+                    "/evolutions/1/target_def_name".to_string(),
+                    "evolves back into itself through tower -> block -> brick -> tower".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn evolving_into_itself_is_a_cycle() {
+        let errors = cycle_errors(&[monster("block", &["block"])]);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].2, "evolves back into itself through block -> block");
+    }
+
+    fn evolved_events(app: &mut App) -> Vec<MonsterEvolved> {
+        app.world.resource_mut::<Events<MonsterEvolved>>().drain().collect()
+    }
+
+    #[test]
+    fn levelling_up_evolves_the_monster() {
+        let (mut app, entity) = app_with_monster(evolving_defs(), 15);
+        app.insert_resource(TimeOfDay::Night);
+
+        gain_levels(&mut app, entity, 20);
+
+        let instance = &app.world.get::<Monster>(entity).unwrap().instance;
+        assert_eq!(instance.def, DefId::from_def_name("brick"));
+        assert_eq!(
+            evolved_events(&mut app),
+            vec![MonsterEvolved {
+                monster: entity,
+                from: DefId::from_def_name("block"),
+                into: DefId::from_def_name("brick"),
+            }]
+        );
+    }
+
+    #[test]
+    fn levelling_up_outside_the_time_of_day_does_not_evolve() {
+        let (mut app, entity) = app_with_monster(evolving_defs(), 15);
+
+        gain_levels(&mut app, entity, 20);
+
+        assert_eq!(app.world.get::<Monster>(entity).unwrap().instance.def, DefId::from_def_name("block"));
+        assert!(evolved_events(&mut app).is_empty());
+    }
+
+    #[test]
+    fn used_items_teach_moves_and_evolve() {
+        let moon_stone = DefId::from_def_name("moon_stone");
+        let mut def = block();
+        def.evolutions = vec![Evolution {
+            target: DefId::from_def_name("brick"),
+            conditions: vec![EvolutionCondition::Item(moon_stone)],
+        }];
+        def.move_learn_set[3].conditions = vec![MoveLearnCondition::TeachingItem(moon_stone)];
+        let mut brick = block();
+        brick.def_name = "brick".to_string();
+        brick.move_learn_set[5].conditions = vec![MoveLearnCondition::OnEvolution];
+        let mut monster_defs = DefDatabase::new();
+        monster_defs.insert(def).unwrap();
+        monster_defs.insert(brick).unwrap();
+        let (mut app, entity) = app_with_monster(monster_defs, 5);
+
+        app.world.send_event(ItemUsed {
+            monster: entity,
+            item: moon_stone,
+            biome: None,
+        });
+        app.update();
+
+        let instance = &app.world.get::<Monster>(entity).unwrap().instance;
+        assert_eq!(instance.def, DefId::from_def_name("brick"));
+        let known: Vec<DefId<MonsterMove>> = instance.known_moves.iter().map(|known| known.monster_move).collect();
+        assert_eq!(
+            known,
+            vec![
+                DefId::from_def_name("tackle"),
+                DefId::from_def_name("growl"),
+                DefId::from_def_name("bite"),
+                DefId::from_def_name("slam"),
+            ]
+        );
+        let offered: Vec<MoveLearnOffered> = app
+            .world
+            .resource_mut::<Events<MoveLearnOffered>>()
+            .drain()
+            .collect();
+        assert_eq!(
+            offered,
+            vec![MoveLearnOffered {
+                monster: entity,
+                monster_move: DefId::from_def_name("roar"),
+            }]
+        );
+        assert_eq!(evolved_events(&mut app).len(), 1);
+    }
+}
@@ -1,20 +1,43 @@
+mod evolution;
 mod monster_definition;
+mod monster_instance;
 mod monster_move;
+mod monster_stats;
 mod monster_type;
+mod move_learning;
+#[cfg(test)]
+mod test_fixtures;
 
+pub use evolution::*;
 pub use monster_definition::*;
+pub use monster_instance::*;
 pub use monster_type::*;
 pub use monster_move::*;
+pub use monster_stats::*;
+pub use move_learning::*;
 
+use crate::def_registry::RegisterDefType;
 use crate::prelude::*;
 
+/// A monster in the world, like a wild one roaming a biome.
 #[derive(Component, Debug)]
 pub struct Monster {
-    def: DefId<MonsterDefinition>,
+    pub instance: MonsterInstance,
 }
 
 pub struct MonsterPlugin;
 
 impl Plugin for MonsterPlugin {
-    fn build(&self, app: &mut App) {}
+    fn build(&self, app: &mut App) {
+        app.register_def_type::<RawMonsterType>()
+            .register_def_type::<RawMonsterMove>()
+            .register_def_type::<MonsterRawDefinition>()
+            .add_event::<ExperienceGained>()
+            .add_event::<ItemUsed>()
+            .add_event::<MonsterLevelledUp>()
+            .add_event::<MoveLearnOffered>()
+            .add_event::<MonsterEvolved>()
+            .add_system(apply_experience.run_if(in_state(GameState::Playing)))
+            .add_system(evolve_monsters.after(apply_experience).run_if(in_state(GameState::Playing)));
+    }
 }
@@ -1,11 +1,17 @@
 use crate::def_database::Definition;
 use crate::def_database::{DefDatabase, DefId};
+use crate::def_errors::{check_range, DefFieldError};
+use crate::def_registry::{LinkContext, RawDefinition};
+use crate::items::ItemDefinition;
 use crate::prelude::*;
 use crate::world::BiomeDefinition;
 use bevy::reflect::TypeUuid;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use crate::monsters::{MonsterMove, MonsterType};
+use crate::monsters::{
+    find_evolution_cycles, Evolution, EvolutionContext, KnownMove, MonsterMove, MonsterStats, MonsterType, RawEvolution,
+    StatGrowth, MIN_HP,
+};
 
 
 
@@ -17,12 +23,82 @@ pub struct MonsterRawDefinition {
     pub spawn_locations: Vec<MonsterRawSpawnLocation>,
     pub move_learn_set: Vec<RawLearnedMove>,
     pub types: Vec<String>,
+    pub base_stats: MonsterStats,
+    pub stat_growth: StatGrowth,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub evolutions: Vec<RawEvolution>,
 }
 
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub enum RawMoveLearnCondition {
     Level(u32),
+    TeachingItem(String),
+    OnEvolution,
+    MinBond(u32),
+    InBiome(String),
+    KnowsMove(String),
+    AllOf(Vec<RawMoveLearnCondition>),
+    AnyOf(Vec<RawMoveLearnCondition>),
+}
+
+impl RawMoveLearnCondition {
+    fn link(&self, context: &LinkContext, pointer: &str) -> Result<MoveLearnCondition, DefFieldError> {
+        let condition = match self {
+            RawMoveLearnCondition::Level(level) => MoveLearnCondition::Level(*level),
+            RawMoveLearnCondition::TeachingItem(item) => {
+                let item = context.resolve::<ItemDefinition>(item, format!("{}/TeachingItem", pointer))?;
+                MoveLearnCondition::TeachingItem(item)
+            }
+            RawMoveLearnCondition::OnEvolution => MoveLearnCondition::OnEvolution,
+            RawMoveLearnCondition::MinBond(bond) => MoveLearnCondition::MinBond(*bond),
+            RawMoveLearnCondition::InBiome(biome) => {
+                let biome = context.resolve::<BiomeDefinition>(biome, format!("{}/InBiome", pointer))?;
+                MoveLearnCondition::InBiome(biome)
+            }
+            RawMoveLearnCondition::KnowsMove(monster_move) => {
+                let monster_move = context.resolve::<MonsterMove>(monster_move, format!("{}/KnowsMove", pointer))?;
+                MoveLearnCondition::KnowsMove(monster_move)
+            }
+            RawMoveLearnCondition::AllOf(conditions) => {
+                MoveLearnCondition::AllOf(link_group(conditions, context, &format!("{}/AllOf", pointer))?)
+            }
+            RawMoveLearnCondition::AnyOf(conditions) => {
+                MoveLearnCondition::AnyOf(link_group(conditions, context, &format!("{}/AnyOf", pointer))?)
+            }
+        };
+        Ok(condition)
+    }
+}
+
+/// Links the conditions of an `AllOf` or `AnyOf` group, where `pointer` points at the group.
+///
+/// An empty group is always or never met, which is more likely a mistake than intended.
+fn link_group(
+    conditions: &[RawMoveLearnCondition],
+    context: &LinkContext,
+    pointer: &str,
+) -> Result<Vec<MoveLearnCondition>, DefFieldError> {
+    if conditions.is_empty() {
+        return Err(DefFieldError::InvalidValue {
+            pointer: pointer.to_string(),
+            message: "needs at least one condition".to_string(),
+        });
+    }
+    link_conditions(conditions, context, pointer)
+}
+
+/// Links a list of conditions, where `pointer` points at the list.
+fn link_conditions(
+    conditions: &[RawMoveLearnCondition],
+    context: &LinkContext,
+    pointer: &str,
+) -> Result<Vec<MoveLearnCondition>, DefFieldError> {
+    conditions
+        .iter()
+        .enumerate()
+        .map(|(i, condition)| condition.link(context, &format!("{}/{}", pointer, i)))
+        .collect()
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
@@ -37,37 +113,32 @@ pub struct MonsterRawSpawnLocation {
     pub biome_def: String,
 }
 
-impl MonsterRawDefinition {
-    pub fn to_definition(
-        &self,
-        asset_server: &Res<AssetServer>,
-        biomes: &DefDatabase<BiomeDefinition>,
-        moves: &DefDatabase<MonsterMove>,
-        types: &DefDatabase<MonsterType>,
-    ) -> Result<MonsterDefinition> {
-        let model = asset_server.load(&self.model_path);
+impl RawDefinition for MonsterRawDefinition {
+    type Definition = MonsterDefinition;
+
+    fn link(&self, context: &LinkContext) -> Result<MonsterDefinition, DefFieldError> {
+        check_range("/base_stats/hp", self.base_stats.hp as f32, MIN_HP as f32, None)?;
+        let model = context.load_asset(&self.model_path, "/model_path")?;
 
         let mut spawn_locations = Vec::new();
 
-        for location in self.spawn_locations.iter() {
-            let biome_def = biomes
-                .get_def_id(&location.biome_def)
-                .ok_or_else(|| anyhow!("Biome definition '{}' not found", location.biome_def))?;
+        for (i, location) in self.spawn_locations.iter().enumerate() {
+            let biome_def = context.resolve::<BiomeDefinition>(&location.biome_def, format!("/spawn_locations/{}/biome_def", i))?;
             spawn_locations.push(MonsterSpawnLocation { biome_def });
         }
 
         let mut move_learn_set = Vec::new();
 
-        for learned_move in self.move_learn_set.iter() {
-            let monster_move = moves
-                .get_def_id(&learned_move.monster_move_def_name)
-                .ok_or_else(|| anyhow!("Monster move '{}' not found", learned_move.monster_move_def_name))?;
-            let mut conditions = Vec::new();
-            for condition in learned_move.conditions.iter() {
-                match condition {
-                    RawMoveLearnCondition::Level(level) => conditions.push(MoveLearnCondition::Level(*level)),
-                }
-            }
+        for (i, learned_move) in self.move_learn_set.iter().enumerate() {
+            let monster_move = context.resolve::<MonsterMove>(
+                &learned_move.monster_move_def_name,
+                format!("/move_learn_set/{}/monster_move_def_name", i),
+            )?;
+            let conditions = link_conditions(
+                &learned_move.conditions,
+                context,
+                &format!("/move_learn_set/{}/conditions", i),
+            )?;
             move_learn_set.push(LearnedMove {
                 monster_move,
                 conditions,
@@ -75,50 +146,226 @@ impl MonsterRawDefinition {
         }
 
         let mut monster_types = Vec::new();
-        for monster_type in self.types.iter() {
-            let monster_type = types
-                .get_def_id(&monster_type)
-                .ok_or_else(|| anyhow!("Monster type '{}' not found", monster_type))?;
+        for (i, monster_type) in self.types.iter().enumerate() {
+            let monster_type = context.resolve::<MonsterType>(monster_type, format!("/types/{}", i))?;
             monster_types.push(monster_type);
         }
 
+        let evolutions = self
+            .evolutions
+            .iter()
+            .enumerate()
+            .map(|(i, evolution)| evolution.link(context, &format!("/evolutions/{}", i)))
+            .collect::<Result<Vec<Evolution>, DefFieldError>>()?;
+
         Ok(MonsterDefinition {
             def_name: self.def_name.clone(),
+            model_path: self.model_path.clone(),
             model,
             spawn_locations,
             move_learn_set,
             types: monster_types,
+            base_stats: self.base_stats,
+            stat_growth: self.stat_growth,
+            evolutions,
         })
     }
+
+    fn check_linked(defs: &[&MonsterDefinition]) -> Vec<(String, DefFieldError)> {
+        find_evolution_cycles(defs)
+    }
+
+    fn add_indexes(database: &mut DefDatabase<MonsterDefinition>) {
+        add_monster_indexes(database);
+    }
+
+    fn load_assets(def: &mut MonsterDefinition, context: &LinkContext) -> Result<(), DefFieldError> {
+        def.model = context.load_asset(&def.model_path, "/model_path")?;
+        Ok(())
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// A condition a monster has to meet to learn a move. The conditions of a learned move must all be met.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum MoveLearnCondition {
+    /// The monster has reached the level.
     Level(u32),
+    /// The item is used on the monster.
+    TeachingItem(DefId<ItemDefinition>),
+    /// The monster evolves.
+    OnEvolution,
+    /// The bond between the monster and its tamer is at least this strong.
+    MinBond(u32),
+    /// The monster is in the biome.
+    InBiome(DefId<BiomeDefinition>),
+    /// The monster knows the move.
+    KnowsMove(DefId<MonsterMove>),
+    /// All of the conditions are met.
+    AllOf(Vec<MoveLearnCondition>),
+    /// At least one of the conditions is met.
+    AnyOf(Vec<MoveLearnCondition>),
+}
+
+impl MoveLearnCondition {
+    /// Whether a monster in the state of `context` meets the condition.
+    pub fn is_met(&self, context: &LearnContext) -> bool {
+        match self {
+            MoveLearnCondition::Level(required) => context.level >= *required,
+            MoveLearnCondition::TeachingItem(item) => context.trigger == LearnTrigger::TeachingItem(*item),
+            MoveLearnCondition::OnEvolution => context.trigger == LearnTrigger::Evolution,
+            MoveLearnCondition::MinBond(required) => context.bond >= *required,
+            MoveLearnCondition::InBiome(biome) => context.biome == Some(*biome),
+            MoveLearnCondition::KnowsMove(monster_move) => {
+                context.known_moves.iter().any(|known| known.monster_move == *monster_move)
+            }
+            MoveLearnCondition::AllOf(conditions) => conditions.iter().all(|condition| condition.is_met(context)),
+            MoveLearnCondition::AnyOf(conditions) => conditions.iter().any(|condition| condition.is_met(context)),
+        }
+    }
+}
+
+/// What made a monster try to learn moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LearnTrigger {
+    LevelUp,
+    Evolution,
+    TeachingItem(DefId<ItemDefinition>),
 }
 
-#[derive(Debug, Clone)]
+/// The state of a monster that move learn conditions are checked against.
+#[derive(Debug, Clone, Copy)]
+pub struct LearnContext<'a> {
+    pub trigger: LearnTrigger,
+    pub level: u32,
+    pub bond: u32,
+    /// The biome the monster is in, if it is in one.
+    pub biome: Option<DefId<BiomeDefinition>>,
+    pub known_moves: &'a [KnownMove],
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct LearnedMove {
     pub monster_move: DefId<MonsterMove>,
     pub conditions: Vec<MoveLearnCondition>,
 }
 
-#[derive(Debug, Clone, Resource)]
+impl LearnedMove {
+    /// Whether a monster in the state of `context` meets all conditions to learn the move.
+    pub fn is_learnable(&self, context: &LearnContext) -> bool {
+        self.conditions.iter().all(|condition| condition.is_met(context))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Resource)]
 pub struct MonsterDefinition {
     pub def_name: String,
+    pub model_path: String,
+    /// Loaded from `model_path`, which is what goes into the def cache.
+    #[serde(skip)]
     pub model: Handle<Scene>,
     pub spawn_locations: Vec<MonsterSpawnLocation>,
     pub move_learn_set: Vec<LearnedMove>,
     pub types: Vec<DefId<MonsterType>>,
+    pub base_stats: MonsterStats,
+    pub stat_growth: StatGrowth,
+    /// Checked in order, so the first possible evolution is the one a monster takes.
+    pub evolutions: Vec<Evolution>,
 }
 
-#[derive(Debug, Clone)]
+impl MonsterDefinition {
+    /// The stats of a monster of this kind at `level`.
+    pub fn stats_at_level(&self, level: u32) -> MonsterStats {
+        self.stat_growth.stats_at_level(&self.base_stats, level)
+    }
+
+    /// The first evolution whose conditions are all met, if any.
+    pub fn find_evolution(&self, context: &EvolutionContext) -> Option<&Evolution> {
+        self.evolutions.iter().find(|evolution| evolution.is_possible(context))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct MonsterSpawnLocation {
     pub biome_def: DefId<BiomeDefinition>,
 }
 
 impl Definition for MonsterDefinition {
+    const TYPE_NAME: &'static str = "Monster";
+
     fn get_def_name(&self) -> &str {
         &self.def_name
     }
 }
+
+/// Index of monsters by the biomes they can spawn in, keyed by `DefId<BiomeDefinition>`.
+pub const MONSTERS_BY_SPAWN_BIOME: &str = "spawn_biome";
+/// Index of monsters by the moves in their learn set, keyed by `DefId<MonsterMove>`.
+pub const MONSTERS_BY_LEARNABLE_MOVE: &str = "learnable_move";
+
+pub fn add_monster_indexes(monsters: &mut DefDatabase<MonsterDefinition>) {
+    monsters.add_index(MONSTERS_BY_SPAWN_BIOME, |monster: &MonsterDefinition| {
+        monster.spawn_locations.iter().map(|location| location.biome_def).collect()
+    });
+    monsters.add_index(MONSTERS_BY_LEARNABLE_MOVE, |monster: &MonsterDefinition| {
+        monster.move_learn_set.iter().map(|learned| learned.monster_move).collect()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(trigger: LearnTrigger, level: u32, bond: u32, known_moves: &[KnownMove]) -> LearnContext<'_> {
+        LearnContext {
+            trigger,
+            level,
+            bond,
+            biome: Some(DefId::from_def_name("plains")),
+            known_moves,
+        }
+    }
+
+    #[test]
+    fn conditions_check_the_state_of_the_monster() {
+        let known = [KnownMove {
+            monster_move: DefId::from_def_name("tackle"),
+            remaining_uses: 10,
+        }];
+        let level_up = context(LearnTrigger::LevelUp, 10, 50, &known);
+
+        assert!(MoveLearnCondition::Level(10).is_met(&level_up));
+        assert!(!MoveLearnCondition::Level(11).is_met(&level_up));
+        assert!(MoveLearnCondition::MinBond(50).is_met(&level_up));
+        assert!(!MoveLearnCondition::MinBond(51).is_met(&level_up));
+        assert!(MoveLearnCondition::InBiome(DefId::from_def_name("plains")).is_met(&level_up));
+        assert!(!MoveLearnCondition::InBiome(DefId::from_def_name("beach")).is_met(&level_up));
+        assert!(MoveLearnCondition::KnowsMove(DefId::from_def_name("tackle")).is_met(&level_up));
+        assert!(!MoveLearnCondition::KnowsMove(DefId::from_def_name("ember")).is_met(&level_up));
+    }
+
+    #[test]
+    fn items_and_evolution_need_their_trigger() {
+        let manual = DefId::from_def_name("ember_manual");
+        let level_up = context(LearnTrigger::LevelUp, 10, 0, &[]);
+        let evolution = context(LearnTrigger::Evolution, 10, 0, &[]);
+        let item = context(LearnTrigger::TeachingItem(manual), 10, 0, &[]);
+
+        assert!(!MoveLearnCondition::OnEvolution.is_met(&level_up));
+        assert!(MoveLearnCondition::OnEvolution.is_met(&evolution));
+        assert!(!MoveLearnCondition::TeachingItem(manual).is_met(&level_up));
+        assert!(!MoveLearnCondition::TeachingItem(DefId::from_def_name("other")).is_met(&item));
+        assert!(MoveLearnCondition::TeachingItem(manual).is_met(&item));
+    }
+
+    #[test]
+    fn groups_combine_their_conditions() {
+        let level_up = context(LearnTrigger::LevelUp, 10, 80, &[]);
+        let met = MoveLearnCondition::Level(5);
+        let unmet = MoveLearnCondition::Level(20);
+
+        assert!(MoveLearnCondition::AnyOf(vec![unmet.clone(), met.clone()]).is_met(&level_up));
+        assert!(!MoveLearnCondition::AnyOf(vec![unmet.clone()]).is_met(&level_up));
+        assert!(MoveLearnCondition::AllOf(vec![met.clone(), MoveLearnCondition::MinBond(80)]).is_met(&level_up));
+        assert!(!MoveLearnCondition::AllOf(vec![met, unmet]).is_met(&level_up));
+    }
+}
@@ -0,0 +1,612 @@
+use crate::items::ItemDefinition;
+use crate::localization::{Locale, LocalizedStrings};
+use crate::monsters::{
+    EvolutionContext, LearnContext, LearnTrigger, MonsterDefinition, MonsterMove, MonsterStats, MAX_LEVEL, MIN_HP,
+};
+use crate::prelude::*;
+use crate::world::{BiomeDefinition, TimeOfDay};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// The most moves a monster can know at once.
+pub const MAX_KNOWN_MOVES: usize = 4;
+/// The highest variance a monster can roll for a stat, as a percentage added on top of the stat.
+pub const MAX_STAT_VARIANCE: u32 = 10;
+
+/// A single monster, like one in the party of the player, in a storage box or in a save file.
+///
+/// Refers to its def by id, so it does not depend on the ECS and can be stored as is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonsterInstance {
+    pub def: DefId<MonsterDefinition>,
+    /// Shown instead of the name of the def when set.
+    pub nickname: Option<String>,
+    pub level: u32,
+    /// The total experience gained, which starts at the experience needed for the level it was rolled at.
+    pub experience: u32,
+    pub current_hp: u32,
+    pub current_mp: u32,
+    /// How strong the bond between the monster and its tamer is, which some moves need to be learned.
+    pub bond: u32,
+    /// Rolled once per monster, so two monsters of the same kind and level still differ a little.
+    pub variance: MonsterStats,
+    pub known_moves: Vec<KnownMove>,
+    pub status: MonsterStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnownMove {
+    pub monster_move: DefId<MonsterMove>,
+    pub remaining_uses: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MonsterStatus {
+    #[default]
+    Healthy,
+    Poisoned,
+    Burned,
+    Paralyzed,
+    Asleep,
+    Fainted,
+}
+
+/// What happened when a monster tried to learn a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LearnOutcome {
+    Learned,
+    AlreadyKnown,
+    /// The monster knows [`MAX_KNOWN_MOVES`] moves already, so one has to be forgotten first.
+    SlotsFull,
+}
+
+/// The total experience a monster needs to reach `level`.
+pub fn experience_for_level(level: u32) -> u32 {
+    level.min(MAX_LEVEL).pow(3)
+}
+
+impl MonsterInstance {
+    /// Rolls a fresh monster of the given kind, at full health and knowing the latest moves it could
+    /// have learned by `level`.
+    pub fn roll(
+        def_id: DefId<MonsterDefinition>,
+        def: &MonsterDefinition,
+        moves: &DefDatabase<MonsterMove>,
+        level: u32,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let level = level.min(MAX_LEVEL);
+        let mut roll_variance = || rng.gen_range(0..=MAX_STAT_VARIANCE);
+        let variance = MonsterStats {
+            hp: roll_variance(),
+            mp: roll_variance(),
+            physical_attack: roll_variance(),
+            physical_defense: roll_variance(),
+            magical_attack: roll_variance(),
+            magical_defense: roll_variance(),
+            speed: roll_variance(),
+        };
+
+        // Moves that need other moves are checked against the moves learned before them.
+        let mut learned_moves: Vec<KnownMove> = Vec::new();
+        for learned in def.move_learn_set.iter() {
+            let context = LearnContext {
+                trigger: LearnTrigger::LevelUp,
+                level,
+                bond: 0,
+                biome: None,
+                known_moves: &learned_moves,
+            };
+            if !learned.is_learnable(&context) {
+                continue;
+            }
+            if let Some(move_def) = moves.get_by_id(&learned.monster_move) {
+                learned_moves.push(KnownMove {
+                    monster_move: learned.monster_move,
+                    remaining_uses: move_def.max_uses,
+                });
+            }
+        }
+        let known_moves = learned_moves[learned_moves.len().saturating_sub(MAX_KNOWN_MOVES)..].to_vec();
+
+        let mut monster = MonsterInstance {
+            def: def_id,
+            nickname: None,
+            level,
+            experience: experience_for_level(level),
+            current_hp: 0,
+            current_mp: 0,
+            bond: 0,
+            variance,
+            known_moves,
+            status: MonsterStatus::Healthy,
+        };
+        let stats = monster.stats(def);
+        monster.current_hp = stats.hp;
+        monster.current_mp = stats.mp;
+        monster
+    }
+
+    /// The stats of the monster at its current level, with its variance applied.
+    pub fn stats(&self, def: &MonsterDefinition) -> MonsterStats {
+        let stats = def.stats_at_level(self.level);
+        let vary = |stat: u32, variance: u32| stat + stat * variance / 100;
+        MonsterStats {
+            hp: vary(stats.hp, self.variance.hp),
+            mp: vary(stats.mp, self.variance.mp),
+            physical_attack: vary(stats.physical_attack, self.variance.physical_attack),
+            physical_defense: vary(stats.physical_defense, self.variance.physical_defense),
+            magical_attack: vary(stats.magical_attack, self.variance.magical_attack),
+            magical_defense: vary(stats.magical_defense, self.variance.magical_defense),
+            speed: vary(stats.speed, self.variance.speed),
+        }
+    }
+
+    /// Adds experience, and levels up for as many levels as the experience now reaches.
+    ///
+    /// The moves of the learn set that are learnable now and not known yet are learned while there are free move
+    /// slots. Returns the unlocked moves that did not fit, which can only be learned by forgetting another move.
+    pub fn gain_experience(
+        &mut self,
+        amount: u32,
+        def: &MonsterDefinition,
+        moves: &DefDatabase<MonsterMove>,
+        biome: Option<DefId<BiomeDefinition>>,
+    ) -> Vec<DefId<MonsterMove>> {
+        let old_level = self.level;
+        let old_stats = self.stats(def);
+        self.experience = self.experience.saturating_add(amount);
+        while self.level < MAX_LEVEL && self.experience >= experience_for_level(self.level + 1) {
+            self.level += 1;
+        }
+        if self.level == old_level {
+            return Vec::new();
+        }
+
+        // The stats grown by the new levels are added to the current health, rather than healing the monster.
+        // A fainted monster stays down until it is healed.
+        let new_stats = self.stats(def);
+        if self.current_hp > 0 && self.status != MonsterStatus::Fainted {
+            self.current_hp += new_stats.hp.saturating_sub(old_stats.hp);
+        }
+        self.current_mp += new_stats.mp.saturating_sub(old_stats.mp);
+
+        self.learn_unlocked_moves(LearnTrigger::LevelUp, def, moves, biome)
+    }
+
+    /// Uses a teaching item on the monster, learning the moves of its learn set that need the item.
+    ///
+    /// Returns the taught moves that did not fit, like [`MonsterInstance::gain_experience`].
+    pub fn use_teaching_item(
+        &mut self,
+        item: DefId<ItemDefinition>,
+        def: &MonsterDefinition,
+        moves: &DefDatabase<MonsterMove>,
+        biome: Option<DefId<BiomeDefinition>>,
+    ) -> Vec<DefId<MonsterMove>> {
+        self.learn_unlocked_moves(LearnTrigger::TeachingItem(item), def, moves, biome)
+    }
+
+    /// Evolves the monster by the first evolution of its def whose conditions are met, if any.
+    ///
+    /// Returns `None` when the monster did not evolve, and the moves that did not fit like
+    /// [`MonsterInstance::evolve`] otherwise.
+    pub fn try_evolve(
+        &mut self,
+        monster_defs: &DefDatabase<MonsterDefinition>,
+        moves: &DefDatabase<MonsterMove>,
+        item: Option<DefId<ItemDefinition>>,
+        biome: Option<DefId<BiomeDefinition>>,
+        time_of_day: TimeOfDay,
+    ) -> Option<Vec<DefId<MonsterMove>>> {
+        let def = monster_defs.get_by_id(&self.def)?;
+        let evolution = def.find_evolution(&self.evolution_context(item, biome, time_of_day))?;
+        let target_def = monster_defs.get_by_id(&evolution.target)?;
+        Some(self.evolve(evolution.target, def, target_def, moves, biome))
+    }
+
+    /// Turns the monster into another kind of monster, keeping its nickname, experience, level and moves.
+    ///
+    /// The stats are recalculated for the new def, keeping the damage the monster has taken, and the moves
+    /// the new def teaches on evolution are learned. Returns the moves that did not fit, like
+    /// [`MonsterInstance::gain_experience`].
+    pub fn evolve(
+        &mut self,
+        target: DefId<MonsterDefinition>,
+        def: &MonsterDefinition,
+        target_def: &MonsterDefinition,
+        moves: &DefDatabase<MonsterMove>,
+        biome: Option<DefId<BiomeDefinition>>,
+    ) -> Vec<DefId<MonsterMove>> {
+        let old_stats = self.stats(def);
+        let new_stats = self.stats(target_def);
+        let hp_lost = old_stats.hp.saturating_sub(self.current_hp);
+        let mp_used = old_stats.mp.saturating_sub(self.current_mp);
+        // Lower stats after evolving should not knock out a monster that was still standing.
+        let min_hp = if self.current_hp > 0 { MIN_HP } else { 0 };
+        self.current_hp = new_stats.hp.saturating_sub(hp_lost).max(min_hp);
+        self.current_mp = new_stats.mp.saturating_sub(mp_used);
+        self.def = target;
+
+        self.learn_unlocked_moves(LearnTrigger::Evolution, target_def, moves, biome)
+    }
+
+    /// The state of the monster and the world to check evolution conditions against.
+    pub fn evolution_context(
+        &self,
+        item: Option<DefId<ItemDefinition>>,
+        biome: Option<DefId<BiomeDefinition>>,
+        time_of_day: TimeOfDay,
+    ) -> EvolutionContext {
+        EvolutionContext {
+            level: self.level,
+            bond: self.bond,
+            item,
+            biome,
+            time_of_day,
+        }
+    }
+
+    /// The state of the monster to check move learn conditions against.
+    pub fn learn_context(&self, trigger: LearnTrigger, biome: Option<DefId<BiomeDefinition>>) -> LearnContext<'_> {
+        LearnContext {
+            trigger,
+            level: self.level,
+            bond: self.bond,
+            biome,
+            known_moves: &self.known_moves,
+        }
+    }
+
+    /// Learns the moves of the learn set that `trigger` unlocks while there are free move slots, returning the
+    /// moves that did not fit.
+    ///
+    /// Levelling up unlocks every learnable move the monster does not know, as conditions like the bond may have
+    /// been met since the last level up. Other triggers only unlock the moves they make the difference for,
+    /// so an item does not teach moves skipped while levelling.
+    ///
+    /// The moves are checked in learn set order against the moves learned so far, so a move that needs
+    /// another move is learned right after it.
+    fn learn_unlocked_moves(
+        &mut self,
+        trigger: LearnTrigger,
+        def: &MonsterDefinition,
+        moves: &DefDatabase<MonsterMove>,
+        biome: Option<DefId<BiomeDefinition>>,
+    ) -> Vec<DefId<MonsterMove>> {
+        let mut offered = Vec::new();
+        for learned in def.move_learn_set.iter() {
+            if self.knows_move(learned.monster_move) || offered.contains(&learned.monster_move) {
+                continue;
+            }
+            let context = self.learn_context(trigger, biome);
+            let level_up = LearnContext {
+                trigger: LearnTrigger::LevelUp,
+                ..context
+            };
+            let unlocked =
+                learned.is_learnable(&context) && (trigger == LearnTrigger::LevelUp || !learned.is_learnable(&level_up));
+            let Some(move_def) = moves.get_by_id(&learned.monster_move).filter(|_| unlocked) else {
+                continue;
+            };
+            if self.learn_move(learned.monster_move, move_def) == LearnOutcome::SlotsFull {
+                offered.push(learned.monster_move);
+            }
+        }
+        offered
+    }
+
+    /// Learns a move into a free move slot, with all its uses left.
+    pub fn learn_move(&mut self, monster_move: DefId<MonsterMove>, move_def: &MonsterMove) -> LearnOutcome {
+        if self.knows_move(monster_move) {
+            return LearnOutcome::AlreadyKnown;
+        }
+        if self.known_moves.len() >= MAX_KNOWN_MOVES {
+            return LearnOutcome::SlotsFull;
+        }
+
+        self.known_moves.push(KnownMove {
+            monster_move,
+            remaining_uses: move_def.max_uses,
+        });
+        LearnOutcome::Learned
+    }
+
+    /// Forgets the move in `slot` to learn another move in its place.
+    ///
+    /// Returns `false` when there is no move in the slot, or the new move is already known.
+    pub fn replace_move(&mut self, slot: usize, monster_move: DefId<MonsterMove>, move_def: &MonsterMove) -> bool {
+        if self.knows_move(monster_move) {
+            return false;
+        }
+        let Some(known) = self.known_moves.get_mut(slot) else {
+            return false;
+        };
+
+        *known = KnownMove {
+            monster_move,
+            remaining_uses: move_def.max_uses,
+        };
+        true
+    }
+
+    pub fn knows_move(&self, monster_move: DefId<MonsterMove>) -> bool {
+        self.known_moves.iter().any(|known| known.monster_move == monster_move)
+    }
+
+    /// The nickname of the monster, or the name of its def when it has none.
+    pub fn name<'a>(&'a self, def: &'a MonsterDefinition, strings: &'a LocalizedStrings, locale: &Locale) -> &'a str {
+        self.nickname.as_deref().unwrap_or_else(|| strings.def_name(locale, def))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monsters::test_fixtures::{block, evolving_defs, moves};
+    use crate::monsters::MoveLearnCondition;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn roll(level: u32, seed: u64) -> MonsterInstance {
+        let def = block();
+        MonsterInstance::roll(DefId::from_def_name("block"), &def, &moves(), level, &mut StdRng::seed_from_u64(seed))
+    }
+
+    fn known_move_names(monster: &MonsterInstance) -> Vec<String> {
+        let moves = moves();
+        monster
+            .known_moves
+            .iter()
+            .map(|known| moves.get_by_id(&known.monster_move).unwrap().def_name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn rolled_monsters_start_at_full_health() {
+        let monster = roll(12, 1);
+        let stats = monster.stats(&block());
+
+        assert_eq!(monster.level, 12);
+        assert_eq!(monster.experience, experience_for_level(12));
+        assert_eq!(monster.current_hp, stats.hp);
+        assert_eq!(monster.current_mp, stats.mp);
+        assert_eq!(monster.status, MonsterStatus::Healthy);
+        assert_eq!(monster.nickname, None);
+    }
+
+    #[test]
+    fn rolled_monsters_know_the_latest_moves_of_their_level() {
+        assert_eq!(known_move_names(&roll(0, 1)), vec!["tackle", "growl"]);
+        assert_eq!(known_move_names(&roll(15, 1)), vec!["growl", "bite", "slam", "crunch"]);
+        let known = &roll(15, 1).known_moves[0];
+        assert_eq!(known.remaining_uses, 11);
+    }
+
+    #[test]
+    fn variance_stays_within_bounds() {
+        for seed in 0..20 {
+            let monster = roll(50, seed);
+            let variance = monster.variance;
+            for value in [
+                variance.hp,
+                variance.mp,
+                variance.physical_attack,
+                variance.physical_defense,
+                variance.magical_attack,
+                variance.magical_defense,
+                variance.speed,
+            ] {
+                assert!(value <= MAX_STAT_VARIANCE);
+            }
+            let base = block().stats_at_level(50);
+            let stats = monster.stats(&block());
+            assert!(stats.hp >= base.hp && stats.hp <= base.hp + base.hp * MAX_STAT_VARIANCE / 100);
+        }
+    }
+
+    #[test]
+    fn monsters_round_trip_through_save_files() {
+        let mut monster = roll(20, 7);
+        monster.nickname = Some("Cubey".to_string());
+        monster.status = MonsterStatus::Poisoned;
+
+        let saved = serde_json::to_string(&monster).unwrap();
+
+        assert_eq!(serde_json::from_str::<MonsterInstance>(&saved).unwrap(), monster);
+    }
+
+    #[test]
+    fn levelling_up_learns_unlocked_moves_into_free_slots() {
+        let def = block();
+        let mut monster = roll(5, 1);
+        assert_eq!(known_move_names(&monster), vec!["tackle", "growl", "bite"]);
+
+        let offered = monster.gain_experience(experience_for_level(10) - monster.experience, &def, &moves(), None);
+
+        assert_eq!(monster.level, 10);
+        assert!(offered.is_empty());
+        assert_eq!(known_move_names(&monster), vec!["tackle", "growl", "bite", "slam"]);
+    }
+
+    #[test]
+    fn moves_that_do_not_fit_are_offered() {
+        let def = block();
+        let mut monster = roll(10, 1);
+
+        let offered = monster.gain_experience(experience_for_level(30) - monster.experience, &def, &moves(), None);
+
+        assert_eq!(monster.level, 30);
+        assert_eq!(offered, vec![DefId::from_def_name("crunch"), DefId::from_def_name("roar")]);
+        assert_eq!(known_move_names(&monster), vec!["tackle", "growl", "bite", "slam"]);
+    }
+
+    #[test]
+    fn levelling_up_adds_the_grown_health() {
+        let def = block();
+        let mut monster = roll(10, 1);
+        let old_stats = monster.stats(&def);
+        monster.current_hp = 1;
+
+        monster.gain_experience(experience_for_level(30) - monster.experience, &def, &moves(), None);
+
+        let grown = monster.stats(&def).hp - old_stats.hp;
+        assert!(grown > 0);
+        assert_eq!(monster.current_hp, 1 + grown);
+    }
+
+    #[test]
+    fn levelling_up_keeps_healthy_monsters_at_full_health() {
+        let def = block();
+        for seed in 0..20 {
+            let mut monster = roll(10, seed);
+
+            monster.gain_experience(experience_for_level(30) - monster.experience, &def, &moves(), None);
+
+            assert_eq!(monster.current_hp, monster.stats(&def).hp);
+            assert_eq!(monster.current_mp, monster.stats(&def).mp);
+        }
+    }
+
+    #[test]
+    fn levelling_up_does_not_revive_fainted_monsters() {
+        let def = block();
+        let mut monster = roll(10, 1);
+        monster.current_hp = 0;
+        monster.status = MonsterStatus::Fainted;
+
+        monster.gain_experience(experience_for_level(30) - monster.experience, &def, &moves(), None);
+
+        assert_eq!(monster.current_hp, 0);
+        assert_eq!(monster.status, MonsterStatus::Fainted);
+    }
+
+    #[test]
+    fn too_little_experience_keeps_the_level() {
+        let def = block();
+        let mut monster = roll(10, 1);
+
+        let offered = monster.gain_experience(1, &def, &moves(), None);
+
+        assert_eq!(monster.level, 10);
+        assert_eq!(monster.experience, experience_for_level(10) + 1);
+        assert!(offered.is_empty());
+    }
+
+    #[test]
+    fn offered_moves_replace_a_forgotten_move() {
+        let moves = moves();
+        let mut monster = roll(15, 1);
+        let roar = DefId::from_def_name("roar");
+        let roar_def = moves.get_by_id(&roar).unwrap();
+
+        assert_eq!(monster.learn_move(roar, roar_def), LearnOutcome::SlotsFull);
+        assert!(monster.replace_move(0, roar, roar_def));
+        assert_eq!(monster.known_moves[0].remaining_uses, roar_def.max_uses);
+        assert_eq!(monster.learn_move(roar, roar_def), LearnOutcome::AlreadyKnown);
+        assert!(!monster.replace_move(1, roar, roar_def));
+    }
+
+    #[test]
+    fn teaching_items_only_teach_the_moves_that_need_them() {
+        let mut def = block();
+        let manual = DefId::from_def_name("roar_manual");
+        def.move_learn_set[5].conditions = vec![MoveLearnCondition::AnyOf(vec![
+            MoveLearnCondition::Level(30),
+            MoveLearnCondition::TeachingItem(manual),
+        ])];
+        let mut monster = roll(5, 1);
+
+        let offered = monster.use_teaching_item(manual, &def, &moves(), None);
+
+        assert!(offered.is_empty());
+        assert_eq!(known_move_names(&monster), vec!["tackle", "growl", "bite", "roar"]);
+    }
+
+    #[test]
+    fn moves_that_need_another_move_are_learned_after_it() {
+        let mut def = block();
+        def.move_learn_set[4].conditions = vec![
+            MoveLearnCondition::Level(5),
+            MoveLearnCondition::KnowsMove(DefId::from_def_name("bite")),
+        ];
+        let moves = moves();
+
+        let monster = MonsterInstance::roll(DefId::from_def_name("block"), &def, &moves, 5, &mut StdRng::seed_from_u64(1));
+
+        assert_eq!(known_move_names(&monster), vec!["tackle", "growl", "bite", "crunch"]);
+    }
+
+    #[test]
+    fn levelling_up_learns_moves_that_need_a_move_learned_on_the_same_level() {
+        let mut def = block();
+        def.move_learn_set[4].conditions = vec![
+            MoveLearnCondition::Level(5),
+            MoveLearnCondition::KnowsMove(DefId::from_def_name("bite")),
+        ];
+        let mut monster = roll(0, 1);
+
+        let offered = monster.gain_experience(experience_for_level(5) - monster.experience, &def, &moves(), None);
+
+        assert!(offered.is_empty());
+        assert_eq!(known_move_names(&monster), vec!["tackle", "growl", "bite", "crunch"]);
+    }
+
+    #[test]
+    fn levelling_up_learns_moves_whose_bond_was_reached_after_their_level() {
+        let mut def = block();
+        def.move_learn_set[5].conditions = vec![MoveLearnCondition::Level(5), MoveLearnCondition::MinBond(50)];
+        let mut monster = roll(5, 1);
+        assert_eq!(known_move_names(&monster), vec!["tackle", "growl", "bite"]);
+        monster.bond = 50;
+
+        monster.gain_experience(experience_for_level(6) - monster.experience, &def, &moves(), None);
+
+        assert_eq!(known_move_names(&monster), vec!["tackle", "growl", "bite", "roar"]);
+    }
+
+    #[test]
+    fn monsters_only_evolve_once_the_conditions_are_met() {
+        let monster_defs = evolving_defs();
+        let mut monster = roll(20, 1);
+
+        assert_eq!(monster.try_evolve(&monster_defs, &moves(), None, None, TimeOfDay::Day), None);
+        monster.level = 19;
+        assert_eq!(monster.try_evolve(&monster_defs, &moves(), None, None, TimeOfDay::Night), None);
+        assert_eq!(monster.def, DefId::from_def_name("block"));
+    }
+
+    #[test]
+    fn evolving_keeps_the_monster_and_recalculates_its_stats() {
+        let monster_defs = evolving_defs();
+        let mut monster = roll(20, 1);
+        monster.nickname = Some("Cubey".to_string());
+        monster.current_hp -= 10;
+        let before = monster.clone();
+        let brick = monster_defs.get_by_name("brick").unwrap();
+
+        let offered = monster.try_evolve(&monster_defs, &moves(), None, None, TimeOfDay::Night);
+
+        assert_eq!(offered, Some(vec![DefId::from_def_name("roar")]));
+        assert_eq!(monster.def, DefId::from_def_name("brick"));
+        assert_eq!(monster.nickname, before.nickname);
+        assert_eq!(monster.known_moves, before.known_moves);
+        assert_eq!(monster.level, before.level);
+        assert_eq!(monster.experience, before.experience);
+        assert_eq!(monster.variance, before.variance);
+        assert_eq!(monster.current_hp, monster.stats(brick).hp - 10);
+        assert!(monster.stats(brick).hp > before.stats(&block()).hp);
+        assert!(monster.stats(brick).speed < before.stats(&block()).speed);
+    }
+
+    #[test]
+    fn evolving_learns_the_moves_of_the_evolution() {
+        let monster_defs = evolving_defs();
+        let mut monster = roll(5, 1);
+        monster.level = 20;
+
+        monster.try_evolve(&monster_defs, &moves(), None, None, TimeOfDay::Night);
+
+        assert_eq!(known_move_names(&monster), vec!["tackle", "growl", "bite", "roar"]);
+    }
+}
@@ -1,45 +1,84 @@
 use bevy::reflect::TypeUuid;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use crate::def_errors::{check_range, DefFieldError};
+use crate::def_registry::{LinkContext, RawDefinition};
 use crate::monsters::MonsterType;
 use crate::prelude::*;
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 pub enum MoveCategory {
     Physical,
     Magical,
 }
 
+const MIN_CHANCE: f32 = 0.0;
+const MAX_CHANCE: f32 = 1.0;
+const MIN_AMOUNT: f32 = 0.0;
+const MIN_CRIT_MULTIPLIER: f32 = 1.0;
+const MIN_USES: u32 = 1;
+
 #[derive(Debug, Deserialize, Serialize, TypeUuid, JsonSchema)]
 #[uuid = "f7f576dd-71f3-40fb-988f-8e676048cbf6"]
 pub struct RawMonsterMove {
     pub def_name: String,
     pub move_type_def_name: String,
+    #[schemars(range(min = "MIN_AMOUNT"))]
     pub base_mp_usage: f32,
+    #[schemars(range(min = "MIN_AMOUNT"))]
     pub base_damage: f32,
+    #[schemars(range(min = "MIN_CHANCE", max = "MAX_CHANCE"))]
     pub base_accuracy: f32,
+    #[schemars(range(min = "MIN_CHANCE", max = "MAX_CHANCE"))]
     pub base_crit_chance: f32,
+    #[schemars(range(min = "MIN_CRIT_MULTIPLIER"))]
     pub base_crit_multiplier: f32,
+    #[schemars(range(min = "MIN_AMOUNT"))]
     pub post_move_speed: f32,
     pub category: MoveCategory,
+    /// How often a monster can use the move before it has to rest.
+    #[schemars(range(min = "MIN_USES"))]
+    pub max_uses: u32,
 }
 
 impl RawMonsterMove {
-    pub fn to_definition(&self, db: &DefDatabase<MonsterType>) -> Result<MonsterMove> {
+    pub fn validate(&self) -> Result<(), DefFieldError> {
+        check_range("/base_mp_usage", self.base_mp_usage, MIN_AMOUNT, None)?;
+        check_range("/base_damage", self.base_damage, MIN_AMOUNT, None)?;
+        check_range("/base_accuracy", self.base_accuracy, MIN_CHANCE, Some(MAX_CHANCE))?;
+        check_range("/base_crit_chance", self.base_crit_chance, MIN_CHANCE, Some(MAX_CHANCE))?;
+        check_range("/base_crit_multiplier", self.base_crit_multiplier, MIN_CRIT_MULTIPLIER, None)?;
+        check_range("/post_move_speed", self.post_move_speed, MIN_AMOUNT, None)?;
+        check_range("/max_uses", self.max_uses as f32, MIN_USES as f32, None)?;
+        Ok(())
+    }
+}
+
+impl RawDefinition for RawMonsterMove {
+    type Definition = MonsterMove;
+
+    fn link(&self, context: &LinkContext) -> Result<MonsterMove, DefFieldError> {
+        self.validate()?;
+
         Ok(MonsterMove {
             def_name: self.def_name.clone(),
-            move_type: db.get_def_id(&self.move_type_def_name).ok_or_else(|| anyhow!("Monster type '{}' not found", self.move_type_def_name))?,
+            move_type: context.resolve::<MonsterType>(&self.move_type_def_name, "/move_type_def_name")?,
             base_mp_usage: self.base_mp_usage,
             base_damage: self.base_damage,
             base_accuracy: self.base_accuracy,
             base_crit_chance: self.base_crit_chance,
             base_crit_multiplier: self.base_crit_multiplier,
             category: self.category,
+            max_uses: self.max_uses,
         })
     }
+
+    fn add_indexes(database: &mut DefDatabase<MonsterMove>) {
+        add_monster_move_indexes(database);
+    }
 }
 
-#[derive(Debug, Clone, Resource)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Resource)]
 pub struct MonsterMove {
     pub def_name: String,
     pub move_type: DefId<MonsterType>,
@@ -49,10 +88,20 @@ pub struct MonsterMove {
     pub base_crit_chance: f32,
     pub base_crit_multiplier: f32,
     pub category: MoveCategory,
+    pub max_uses: u32,
 }
 
 impl Definition for MonsterMove {
+    const TYPE_NAME: &'static str = "MonsterMove";
+
     fn get_def_name(&self) -> &str {
         &self.def_name
     }
 }
+
+/// Index of moves by their type, keyed by `DefId<MonsterType>`.
+pub const MOVES_BY_TYPE: &str = "move_type";
+
+pub fn add_monster_move_indexes(moves: &mut DefDatabase<MonsterMove>) {
+    moves.add_index(MOVES_BY_TYPE, |monster_move: &MonsterMove| vec![monster_move.move_type]);
+}
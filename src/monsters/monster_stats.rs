@@ -0,0 +1,162 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The highest level a monster can reach, where every growth curve has reached its full growth.
+pub const MAX_LEVEL: u32 = 100;
+/// How many times its base value a stat has grown by at [`MAX_LEVEL`].
+const MAX_LEVEL_GROWTH: f32 = 4.0;
+/// How far the erratic curve swings ahead of and behind the linear curve.
+const ERRATIC_SWING: f32 = 0.1;
+pub(crate) const MIN_HP: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct MonsterStats {
+    #[schemars(range(min = "MIN_HP"))]
+    pub hp: u32,
+    pub mp: u32,
+    pub physical_attack: u32,
+    pub physical_defense: u32,
+    pub magical_attack: u32,
+    pub magical_defense: u32,
+    pub speed: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub enum GrowthCurve {
+    Linear,
+    Fast,
+    Slow,
+    Erratic,
+}
+
+impl GrowthCurve {
+    /// The share of the full growth reached at `level`, going from 0 at level 0 to 1 at [`MAX_LEVEL`].
+    ///
+    /// Fast curves reach most of their growth early, slow curves late, and erratic curves run ahead of
+    /// linear growth in the first half and behind it in the second.
+    pub fn progress(self, level: u32) -> f32 {
+        let t = level.min(MAX_LEVEL) as f32 / MAX_LEVEL as f32;
+        match self {
+            GrowthCurve::Linear => t,
+            GrowthCurve::Fast => 1.0 - (1.0 - t) * (1.0 - t),
+            GrowthCurve::Slow => t * t,
+            GrowthCurve::Erratic => t + ERRATIC_SWING * (std::f32::consts::TAU * t).sin(),
+        }
+    }
+
+    /// The value of a stat with the given base value at `level`.
+    pub fn stat_at_level(self, base: u32, level: u32) -> u32 {
+        (base as f32 * (1.0 + MAX_LEVEL_GROWTH * self.progress(level))).round() as u32
+    }
+}
+
+/// The growth curve of each stat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct StatGrowth {
+    pub hp: GrowthCurve,
+    pub mp: GrowthCurve,
+    pub physical_attack: GrowthCurve,
+    pub physical_defense: GrowthCurve,
+    pub magical_attack: GrowthCurve,
+    pub magical_defense: GrowthCurve,
+    pub speed: GrowthCurve,
+}
+
+impl StatGrowth {
+    /// Grows every base stat along its own curve.
+    pub fn stats_at_level(&self, base: &MonsterStats, level: u32) -> MonsterStats {
+        MonsterStats {
+            hp: self.hp.stat_at_level(base.hp, level),
+            mp: self.mp.stat_at_level(base.mp, level),
+            physical_attack: self.physical_attack.stat_at_level(base.physical_attack, level),
+            physical_defense: self.physical_defense.stat_at_level(base.physical_defense, level),
+            magical_attack: self.magical_attack.stat_at_level(base.magical_attack, level),
+            magical_defense: self.magical_defense.stat_at_level(base.magical_defense, level),
+            speed: self.speed.stat_at_level(base.speed, level),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [GrowthCurve; 4] = [GrowthCurve::Linear, GrowthCurve::Fast, GrowthCurve::Slow, GrowthCurve::Erratic];
+
+    fn all_linear_except_speed(speed: GrowthCurve) -> StatGrowth {
+        StatGrowth {
+            hp: GrowthCurve::Linear,
+            mp: GrowthCurve::Linear,
+            physical_attack: GrowthCurve::Linear,
+            physical_defense: GrowthCurve::Linear,
+            magical_attack: GrowthCurve::Linear,
+            magical_defense: GrowthCurve::Linear,
+            speed,
+        }
+    }
+
+    fn base_stats() -> MonsterStats {
+        MonsterStats {
+            hp: 40,
+            mp: 20,
+            physical_attack: 12,
+            physical_defense: 10,
+            magical_attack: 8,
+            magical_defense: 9,
+            speed: 10,
+        }
+    }
+
+    #[test]
+    fn curves_go_from_nothing_to_full_growth() {
+        for curve in CURVES {
+            assert!(curve.progress(0).abs() < 1e-6, "{:?}", curve);
+            assert!((curve.progress(MAX_LEVEL) - 1.0).abs() < 1e-6, "{:?}", curve);
+        }
+    }
+
+    #[test]
+    fn curves_never_shrink_stats() {
+        for curve in CURVES {
+            for level in 0..MAX_LEVEL {
+                assert!(curve.progress(level + 1) >= curve.progress(level), "{:?} at level {}", curve, level);
+            }
+        }
+    }
+
+    #[test]
+    fn fast_curves_lead_and_slow_curves_trail() {
+        let halfway = MAX_LEVEL / 2;
+        assert!(GrowthCurve::Fast.progress(halfway) > GrowthCurve::Linear.progress(halfway));
+        assert!(GrowthCurve::Slow.progress(halfway) < GrowthCurve::Linear.progress(halfway));
+    }
+
+    #[test]
+    fn erratic_curves_lead_early_and_trail_late() {
+        let early = MAX_LEVEL / 4;
+        let late = MAX_LEVEL * 3 / 4;
+        assert!(GrowthCurve::Erratic.progress(early) > GrowthCurve::Linear.progress(early));
+        assert!(GrowthCurve::Erratic.progress(late) < GrowthCurve::Linear.progress(late));
+    }
+
+    #[test]
+    fn levels_past_the_max_level_stop_growing() {
+        for curve in CURVES {
+            assert_eq!(curve.stat_at_level(10, MAX_LEVEL + 20), curve.stat_at_level(10, MAX_LEVEL));
+        }
+    }
+
+    #[test]
+    fn stats_grow_along_their_own_curve() {
+        let base = base_stats();
+        let growth = all_linear_except_speed(GrowthCurve::Slow);
+
+        assert_eq!(growth.stats_at_level(&base, 0), base);
+        let halfway = growth.stats_at_level(&base, MAX_LEVEL / 2);
+        assert_eq!(halfway.hp, 120);
+        assert_eq!(halfway.speed, 20);
+        let max = growth.stats_at_level(&base, MAX_LEVEL);
+        assert_eq!(max.hp, 200);
+        assert_eq!(max.speed, 50);
+    }
+}
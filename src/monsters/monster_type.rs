@@ -1,3 +1,5 @@
+use crate::def_errors::{check_range, DefFieldError};
+use crate::def_registry::{LinkContext, RawDefinition};
 use crate::prelude::*;
 use bevy::reflect::TypeUuid;
 use schemars::JsonSchema;
@@ -10,27 +12,27 @@ pub struct RawMonsterType {
     pub damage_scales: Vec<RawMonsterTypeDamageScale>,
 }
 
+const MIN_DAMAGE_SCALE: f32 = 0.0;
+
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct RawMonsterTypeDamageScale {
+    #[schemars(range(min = "MIN_DAMAGE_SCALE"))]
     pub damage_scale: f32,
     pub target_type_def_name: String,
 }
 
-impl RawMonsterType {
-    pub fn to_definition(&self) -> MonsterType {
-        MonsterType {
-            def_name: self.def_name.clone(),
-            damage_scales: vec![]
-        }
-    }
-    
-    pub fn link_definitions(&self, monster_types: &DefDatabase<MonsterType>) -> Result<MonsterType> {
+impl RawDefinition for RawMonsterType {
+    type Definition = MonsterType;
+
+    fn link(&self, context: &LinkContext) -> Result<MonsterType, DefFieldError> {
         let mut damage_scales = Vec::new();
         
-        for scale in self.damage_scales.iter() {
-            let target_type = monster_types
-                .get_def_id(&scale.target_type_def_name)
-                .ok_or_else(|| anyhow!("Monster type '{}' not found", scale.target_type_def_name))?;
+        for (i, scale) in self.damage_scales.iter().enumerate() {
+            check_range(&format!("/damage_scales/{}/damage_scale", i), scale.damage_scale, MIN_DAMAGE_SCALE, None)?;
+            let target_type = context.resolve::<MonsterType>(
+                &scale.target_type_def_name,
+                format!("/damage_scales/{}/target_type_def_name", i),
+            )?;
             damage_scales.push(MonsterTypeDamageScale {
                 damage_scale: scale.damage_scale,
                 target_type,
@@ -44,19 +46,21 @@ impl RawMonsterType {
     }
 }
 
-#[derive(Debug, Clone, Resource)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Resource)]
 pub struct MonsterType {
     pub def_name: String,
     pub damage_scales: Vec<MonsterTypeDamageScale>,
 }
 
 impl Definition for MonsterType {
+    const TYPE_NAME: &'static str = "MonsterType";
+
     fn get_def_name(&self) -> &str {
         &self.def_name
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct MonsterTypeDamageScale {
     pub damage_scale: f32,
     pub target_type: DefId<MonsterType>,
@@ -0,0 +1,129 @@
+use crate::monsters::{Monster, MonsterDefinition, MonsterMove};
+use crate::prelude::*;
+use crate::world::BiomeDefinition;
+
+/// Send to give a monster experience, which levels it up and teaches it the moves of its new levels.
+///
+/// A monster that levels up evolves right away when the conditions of one of its evolutions are met, see
+/// [`crate::monsters::evolve_monsters`].
+pub struct ExperienceGained {
+    pub monster: Entity,
+    pub amount: u32,
+    /// The biome the monster is in, for moves that can only be learned and monsters that can only evolve there.
+    pub biome: Option<DefId<BiomeDefinition>>,
+}
+
+/// Sent when a monster reached a new level by gaining experience.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonsterLevelledUp {
+    pub monster: Entity,
+    /// The biome the monster is in, from the [`ExperienceGained`] event.
+    pub biome: Option<DefId<BiomeDefinition>>,
+}
+
+/// Sent when a monster unlocked a move by levelling up, using an item or evolving, but already knows as many
+/// moves as it can.
+///
+/// The UI can ask which move to forget, and teach the offered move through [`MonsterInstance::replace_move`].
+///
+/// [`MonsterInstance::replace_move`]: crate::monsters::MonsterInstance::replace_move
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveLearnOffered {
+    pub monster: Entity,
+    pub monster_move: DefId<MonsterMove>,
+}
+
+pub fn apply_experience(
+    mut gained_events: EventReader<ExperienceGained>,
+    mut offered_events: EventWriter<MoveLearnOffered>,
+    mut levelled_up_events: EventWriter<MonsterLevelledUp>,
+    mut monsters: Query<&mut Monster>,
+    monster_defs: Res<DefDatabase<MonsterDefinition>>,
+    moves: Res<DefDatabase<MonsterMove>>,
+) {
+    for gained in gained_events.iter() {
+        let Ok(mut monster) = monsters.get_mut(gained.monster) else {
+            continue;
+        };
+        let Some(def) = monster_defs.get_by_id(&monster.instance.def) else {
+            warn!("Monster {:?} has no def, so it cannot gain experience", gained.monster);
+            continue;
+        };
+
+        let old_level = monster.instance.level;
+        let offered = monster.instance.gain_experience(gained.amount, def, &moves, gained.biome);
+
+        if monster.instance.level > old_level {
+            levelled_up_events.send(MonsterLevelledUp {
+                monster: gained.monster,
+                biome: gained.biome,
+            });
+        }
+
+        for monster_move in offered {
+            offered_events.send(MoveLearnOffered {
+                monster: gained.monster,
+                monster_move,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monsters::test_fixtures::{app_with_monster, block, gain_levels};
+    use bevy::ecs::event::Events;
+
+    #[test]
+    fn full_move_slots_offer_the_unlocked_move() {
+        let mut monster_defs = DefDatabase::new();
+        monster_defs.insert(block()).unwrap();
+        let (mut app, entity) = app_with_monster(monster_defs, 10);
+
+        gain_levels(&mut app, entity, 30);
+
+        assert_eq!(app.world.get::<Monster>(entity).unwrap().instance.level, 30);
+        let offered: Vec<MoveLearnOffered> = app
+            .world
+            .resource_mut::<Events<MoveLearnOffered>>()
+            .drain()
+            .collect();
+        assert_eq!(
+            offered,
+            vec![
+                MoveLearnOffered {
+                    monster: entity,
+                    monster_move: DefId::from_def_name("crunch"),
+                },
+                MoveLearnOffered {
+                    monster: entity,
+                    monster_move: DefId::from_def_name("roar"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn only_new_levels_are_announced() {
+        let mut monster_defs = DefDatabase::new();
+        monster_defs.insert(block()).unwrap();
+        let (mut app, entity) = app_with_monster(monster_defs, 10);
+
+        gain_levels(&mut app, entity, 10);
+        gain_levels(&mut app, entity, 12);
+
+        let levelled_up: Vec<MonsterLevelledUp> = app
+            .world
+            .resource_mut::<Events<MonsterLevelledUp>>()
+            .drain()
+            .collect();
+        assert_eq!(
+            levelled_up,
+            vec![MonsterLevelledUp {
+                monster: entity,
+                biome: None,
+            }]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::monsters::{
+    apply_experience, evolve_monsters, experience_for_level, Evolution, EvolutionCondition, ExperienceGained,
+    GrowthCurve, ItemUsed, LearnedMove, Monster, MonsterDefinition, MonsterEvolved, MonsterInstance,
+    MonsterLevelledUp, MonsterMove, MonsterStats, MoveCategory, MoveLearnCondition, MoveLearnOffered, StatGrowth,
+};
+use crate::prelude::*;
+use crate::world::TimeOfDay;
+
+pub fn monster_move(def_name: &str, max_uses: u32) -> MonsterMove {
+    MonsterMove {
+        def_name: def_name.to_string(),
+        move_type: DefId::from_def_name("normal"),
+        base_mp_usage: 0.0,
+        base_damage: 40.0,
+        base_accuracy: 1.0,
+        base_crit_chance: 0.05,
+        base_crit_multiplier: 2.0,
+        category: MoveCategory::Physical,
+        max_uses,
+    }
+}
+
+/// The moves of [`block`], where each move has one use more than the move before it, starting at 10.
+pub fn moves() -> DefDatabase<MonsterMove> {
+    let mut moves = DefDatabase::new();
+    for (i, def_name) in ["tackle", "growl", "bite", "slam", "crunch", "roar"].iter().enumerate() {
+        moves.insert(monster_move(def_name, 10 + i as u32)).unwrap();
+    }
+    moves
+}
+
+pub fn learned_at(def_name: &str, level: u32) -> LearnedMove {
+    LearnedMove {
+        monster_move: DefId::from_def_name(def_name),
+        conditions: vec![MoveLearnCondition::Level(level)],
+    }
+}
+
+/// A monster learning tackle and growl at level 0, bite at 5, slam at 10, crunch at 15 and roar at 30.
+pub fn block() -> MonsterDefinition {
+    MonsterDefinition {
+        def_name: "block".to_string(),
+        model_path: "monsters/001/model.glb#Scene0".to_string(),
+        model: Handle::default(),
+        spawn_locations: Vec::new(),
+        move_learn_set: vec![
+            learned_at("tackle", 0),
+            learned_at("growl", 0),
+            learned_at("bite", 5),
+            learned_at("slam", 10),
+            learned_at("crunch", 15),
+            learned_at("roar", 30),
+        ],
+        types: Vec::new(),
+        base_stats: MonsterStats {
+            hp: 45,
+            mp: 20,
+            physical_attack: 12,
+            physical_defense: 14,
+            magical_attack: 6,
+            magical_defense: 10,
+            speed: 8,
+        },
+        stat_growth: StatGrowth {
+            hp: GrowthCurve::Slow,
+            mp: GrowthCurve::Linear,
+            physical_attack: GrowthCurve::Linear,
+            physical_defense: GrowthCurve::Fast,
+            magical_attack: GrowthCurve::Slow,
+            magical_defense: GrowthCurve::Linear,
+            speed: GrowthCurve::Erratic,
+        },
+        evolutions: Vec::new(),
+    }
+}
+
+/// A block that evolves into a brick at level 20 during the night, which learns roar on evolution.
+pub fn evolving_defs() -> DefDatabase<MonsterDefinition> {
+    let mut def = block();
+    def.evolutions = vec![Evolution {
+        target: DefId::from_def_name("brick"),
+        conditions: vec![EvolutionCondition::Level(20), EvolutionCondition::TimeOfDay(TimeOfDay::Night)],
+    }];
+    let mut brick = block();
+    brick.def_name = "brick".to_string();
+    brick.base_stats.hp = 60;
+    brick.base_stats.speed = 4;
+    brick.move_learn_set[5].conditions = vec![MoveLearnCondition::OnEvolution];
+
+    let mut monster_defs = DefDatabase::new();
+    monster_defs.insert(def).unwrap();
+    monster_defs.insert(brick).unwrap();
+    monster_defs
+}
+
+/// An app running the monster systems for a block of `monster_defs` rolled at `level`.
+pub fn app_with_monster(monster_defs: DefDatabase<MonsterDefinition>, level: u32) -> (App, Entity) {
+    let moves = moves();
+    let def_id = DefId::from_def_name("block");
+    let instance = MonsterInstance::roll(
+        def_id,
+        monster_defs.get_by_id(&def_id).unwrap(),
+        &moves,
+        level,
+        &mut rand::thread_rng(),
+    );
+
+    let mut app = App::new();
+    app.add_event::<ExperienceGained>()
+        .add_event::<ItemUsed>()
+        .add_event::<MonsterLevelledUp>()
+        .add_event::<MoveLearnOffered>()
+        .add_event::<MonsterEvolved>()
+        .insert_resource(monster_defs)
+        .insert_resource(moves)
+        .init_resource::<TimeOfDay>()
+        .add_system(apply_experience)
+        .add_system(evolve_monsters.after(apply_experience));
+    let entity = app.world.spawn(Monster { instance }).id();
+    (app, entity)
+}
+
+pub fn gain_levels(app: &mut App, entity: Entity, level: u32) {
+    let experience = app.world.get::<Monster>(entity).unwrap().instance.experience;
+    app.world.send_event(ExperienceGained {
+        monster: entity,
+        amount: experience_for_level(level) - experience,
+        biome: None,
+    });
+    app.update();
+}
@@ -4,6 +4,19 @@ use crate::prelude::*;
 pub enum GameState {
     #[default]
     LoadingFromDisk,
+    /// Loading the defs from the def cache, which skips [`GameState::LoadingDefs`] when it is up to date.
+    CheckingDefCache,
+    LoadingDefs,
     AddingToDatabase,
+    /// Waiting for the assets the defs refer to, to check their sub-asset labels.
+    ///
+    /// A state of its own rather than part of [`GameState::LoadingFromDisk`], as the labels can only be checked
+    /// once the files have loaded, which happens after the defs are linked, whether they came from the def
+    /// files or from the def cache. Whether the files exist is already checked while linking.
+    ValidatingAssets,
+    /// The defs had errors, and [`crate::json_asset_definition::DefLoadStrictness::Strict`] is in effect.
+    ///
+    /// The game stops here, showing the [`crate::def_errors::DefLoadReport`] on screen.
+    DefLoadFailed,
     Playing,
 }
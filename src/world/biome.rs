@@ -1,21 +1,51 @@
+use crate::def_errors::{check_bounds_order, DefFieldError};
+use crate::def_registry::{LinkContext, RawDefinition};
 use crate::prelude::*;
 use bevy::reflect::TypeUuid;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Deserialize, Serialize, TypeUuid, JsonSchema, Resource)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, TypeUuid, JsonSchema, Resource)]
 #[uuid = "ceeae331-a37a-428c-aa39-0dd85152d090"]
 pub struct BiomeDefinition {
     pub def_name: String,
+    /// Must not be greater than `max_height`.
     pub min_height: Option<f32>,
     pub max_height: Option<f32>,
+    /// Must not be greater than `max_humidity`.
     pub min_humidity: Option<f32>,
     pub max_humidity: Option<f32>,
+    /// Must not be greater than `max_temperature`.
     pub min_temperature: Option<f32>,
     pub max_temperature: Option<f32>,
 }
 
+impl BiomeDefinition {
+    pub fn validate(&self) -> Result<(), DefFieldError> {
+        check_bounds_order("/min_height", self.min_height, "/max_height", self.max_height)?;
+        check_bounds_order("/min_humidity", self.min_humidity, "/max_humidity", self.max_humidity)?;
+        check_bounds_order(
+            "/min_temperature",
+            self.min_temperature,
+            "/max_temperature",
+            self.max_temperature,
+        )?;
+        Ok(())
+    }
+}
+
+impl RawDefinition for BiomeDefinition {
+    type Definition = BiomeDefinition;
+
+    fn link(&self, _context: &LinkContext) -> Result<BiomeDefinition, DefFieldError> {
+        self.validate()?;
+        Ok(self.clone())
+    }
+}
+
 impl Definition for BiomeDefinition {
+    const TYPE_NAME: &'static str = "Biome";
+
     fn get_def_name(&self) -> &str {
         &self.def_name
     }
@@ -1,4 +1,20 @@
 mod biome;
+mod time_of_day;
 mod world_generator;
 
 pub use biome::*;
+pub use time_of_day::*;
+
+use crate::def_registry::RegisterDefType;
+use crate::prelude::*;
+
+pub struct WorldPlugin;
+
+impl Plugin for WorldPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_def_type::<BiomeDefinition>()
+            .init_resource::<TimeOfDay>()
+            .init_resource::<DayClock>()
+            .add_system(advance_time_of_day.run_if(in_state(GameState::Playing)));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How many seconds each part of the day lasts.
+pub const PART_OF_DAY_SECONDS: f32 = 300.0;
+
+/// The part of the day it currently is in the world.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize, JsonSchema, Resource)]
+pub enum TimeOfDay {
+    Morning,
+    #[default]
+    Day,
+    Evening,
+    Night,
+}
+
+impl TimeOfDay {
+    /// The part of the day that follows this one.
+    pub fn next(self) -> TimeOfDay {
+        match self {
+            TimeOfDay::Morning => TimeOfDay::Day,
+            TimeOfDay::Day => TimeOfDay::Evening,
+            TimeOfDay::Evening => TimeOfDay::Night,
+            TimeOfDay::Night => TimeOfDay::Morning,
+        }
+    }
+}
+
+/// Counts down to the next part of the day.
+#[derive(Debug, Resource)]
+pub struct DayClock {
+    pub timer: Timer,
+}
+
+impl Default for DayClock {
+    fn default() -> Self {
+        DayClock {
+            timer: Timer::from_seconds(PART_OF_DAY_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+pub fn advance_time_of_day(time: Res<Time>, mut clock: ResMut<DayClock>, mut time_of_day: ResMut<TimeOfDay>) {
+    clock.timer.tick(time.delta());
+    for _ in 0..clock.timer.times_finished_this_tick() {
+        *time_of_day = time_of_day.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn the_clock_moves_on_to_the_next_parts_of_the_day() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<DayClock>()
+            .insert_resource(TimeOfDay::Evening)
+            .add_system(advance_time_of_day);
+        let start = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        app.update();
+        assert_eq!(*app.world.resource::<TimeOfDay>(), TimeOfDay::Evening);
+
+        let elapsed = Duration::from_secs_f32(PART_OF_DAY_SECONDS * 2.5);
+        app.world.resource_mut::<Time>().update_with_instant(start + elapsed);
+        app.update();
+
+        assert_eq!(*app.world.resource::<TimeOfDay>(), TimeOfDay::Morning);
+    }
+}